@@ -1,5 +1,5 @@
 //! About page with stats about the package version and the loaded graph.
-use crate::rdf::{GraphEnum, graph, titles, types};
+use crate::rdf::{GraphEnum, kb};
 use bytesize::ByteSize;
 use deepsize::DeepSizeOf;
 use sophia::api::graph::Graph;
@@ -14,22 +14,27 @@ pub struct About {
     pub titles_size: String,
     pub types_size: String,
     pub graph_size: Option<String>,
+    /// Knowledge base files, directories and URLs with the number of triples that each of them added.
+    pub sources: Vec<(String, usize)>,
 }
 
 impl About {
     pub fn new() -> About {
-        let graph_size = match graph() {
+        let kb = kb();
+        let graph_size = match &kb.graph {
             #[cfg(feature = "hdt")]
             GraphEnum::HdtGraph(hdt_graph) => Some(ByteSize(hdt_graph.size_in_bytes() as u64).to_string()),
             GraphEnum::FastGraph(g) => Some(format!("~{} triples", g.triples().size_hint().0)),
+            GraphEnum::EndpointGraph(g) => Some(format!("remote SPARQL endpoint {}", g.endpoint)),
         };
         About {
             cargo_pkg_version: VERSION,
-            num_titles: titles().len(),
-            num_types: types().len(),
-            types_size: ByteSize(types().deep_size_of() as u64).to_string(),
-            titles_size: ByteSize(titles().deep_size_of() as u64).to_string(),
+            num_titles: kb.titles().len(),
+            num_types: kb.types().len(),
+            types_size: ByteSize(kb.types().deep_size_of() as u64).to_string(),
+            titles_size: ByteSize(kb.titles().deep_size_of() as u64).to_string(),
             graph_size,
+            sources: kb.sources.clone(),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::rdf::{Piri, graph, titles};
+use crate::rdf::{Kb, Piri, escape, kb};
 use multimap::MultiMap;
 use sophia::api::MownStr;
 use sophia::api::term::IriRef;
@@ -8,31 +8,31 @@ use std::collections::HashSet;
 
 type IriM<'a> = IriRef<MownStr<'a>>;
 
-fn node(class: &IriM<'_>, subclasses: &MultiMap<&IriM<'_>, &IriM<'_>>) -> (String, u32) {
+fn node(class: &IriM<'_>, subclasses: &MultiMap<&IriM<'_>, &IriM<'_>>, kb: &Kb) -> (String, u32) {
     let piri = Piri::from(class);
-    let title = if let Some(title) = titles().get(&piri.to_string()) { title } else { &piri.short() };
+    let title = kb.title(&piri.to_string()).map_or_else(|| piri.short(), |title| escape(&title));
     let mut inner = String::new();
     let mut count = 0;
     let s = match subclasses.get_vec(class) {
         Some(children) => {
             for child in children {
-                let (child_s, child_count) = &node(child, subclasses);
+                let (child_s, child_count) = &node(child, subclasses, kb);
                 inner += child_s;
                 count += child_count + 1;
             }
             format!(
-                "<details style='margin: 1em;'><summary><a href='{}' target='_blank'>{} ({count})</summary>{inner}</details>\n",
-                piri.root_relative(),
-                title
+                "<details style='margin: 1em;'><summary>{}</summary>{inner}</details>\n",
+                piri.anchor(&format!("{title} ({count})"), " target='_blank'")
             )
         }
-        None => format!("<p style='margin: 1em;'>&bull; <a href='{}' target='_blank'>{}</p>", piri.root_relative(), title),
+        None => format!("<p style='margin: 1em;'>&bull; {}</p>", piri.anchor(&title, " target='_blank'")),
     };
     (s, count)
 }
 
 pub fn class_tree() -> String {
-    let g = graph();
+    let kb = kb();
+    let g = &kb.graph;
     // the graphs we use should never fail
     // rdfs:subclassOf is also used with blank nodes for owl restrictions that we ignore
     let pairs: Vec<[IriM<'_>; 2]> = g
@@ -55,7 +55,7 @@ pub fn class_tree() -> String {
     let mut s = String::new();
     s += "<html><body>";
     for root in roots {
-        s += &node(root, &subclasses).0;
+        s += &node(root, &subclasses, &kb).0;
     }
     s += "<body></html>";
     s
@@ -1,13 +1,16 @@
+use clap::{Parser, Subcommand};
 use config::{ConfigError, Environment, File, FileFormat};
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
 use sophia::iri::Iri;
 use std::collections::{HashMap, HashSet};
 use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::OnceLock;
 
 #[derive(Serialize, Deserialize, Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     /// Server-side common path prefix (scope) that normally matches the last part of the namespace but may also be "" e.g. for local testing.
     /// For example, port 8080, empty base (default) and namespace <http://ab.com/d/> would serve <http://ab.com/d/X> at <localhost:8080/X>.
@@ -17,8 +20,20 @@ pub struct Config {
     pub base: String,
     pub title: Option<String>,
     pub subtitle: Option<String>,
-    pub kb_file: Option<String>,
+    /// Knowledge base files, directories and http(s) URLs that are merged into a single graph.
+    /// A single string is also accepted, environment variables separate them by spaces.
+    /// If empty, the example knowledge base is loaded.
+    #[serde(default, deserialize_with = "string_or_list::deserialize")]
+    pub kb_file: Vec<String>,
     pub port: u16,
+    /// Addresses the server listens on, either IP addresses or host names with the configured port, socket addresses like `[::1]:8080` or Unix domain sockets like `unix:/run/rickview.sock`.
+    /// For example, `["127.0.0.1", "::1"]` only accepts local connections. A single string is also accepted, environment variables separate them by spaces.
+    #[serde(deserialize_with = "string_or_list::deserialize")]
+    pub bind: Vec<String>,
+    /// PEM certificate chain file. If set together with `tls_key`, HTTPS is served instead of HTTP on all addresses except Unix domain sockets.
+    pub tls_cert: Option<String>,
+    /// PEM private key file, see `tls_cert`.
+    pub tls_key: Option<String>,
     pub github: Option<String>,
     pub prefix: Box<str>,
     #[serde(with = "iri_serde")]
@@ -31,18 +46,81 @@ pub struct Config {
     pub langs: Vec<String>,
     pub homepage: Option<String>,
     pub endpoint: Option<String>,
+    /// Query the SPARQL endpoint for each resource instead of loading `kb_file`. Titles and types are not indexed then.
+    pub endpoint_backend: bool,
     /// Show inverse triples, which use the given URI as object instead of subject. May be slow on very large kbs.
     pub show_inverse: bool,
-    /// When false, knowledge base will only be loaded on first resource (non-index) access.
+    /// Start loading the knowledge base on startup instead of on the first request that needs it, see `/readyz`.
+    pub preload: bool,
     pub doc: Option<String>,
     pub log_level: Option<String>,
     pub cargo_pkg_version: String,
+    /// Directory with HTML templates that replace the built-in ones of the same name, such as `header.html`.
+    pub templates: Option<String>,
     /// if data/body.html is present, it is inserted into index.html on rendering
     pub body: Option<String>,
     // override CSS, for example the font
     pub css: Option<String>,
     /// disable memory and CPU intensive preprocessing on large knowledge bases
     pub large: bool,
+    /// Save the titles and types to this file and reuse them on restart while the knowledge base files and the relevant config are unchanged.
+    /// Also enables them for large knowledge bases.
+    pub index_cache: Option<String>,
+    /// Look up the title and type of each shown resource when they are not indexed because of `large` or `endpoint_backend`.
+    pub lazy_titles: bool,
+    /// Maximum number of resources whose looked up title and type are kept in memory.
+    pub lazy_cache_size: usize,
+    /// Redirect resource URIs to the URLs of the documents that describe them, see [`DocumentUrls`].
+    pub document_urls: DocumentUrls,
+    /// Which triples the RDF serializations of a resource contain, see [`BoundedDescription`].
+    pub bounded_description: BoundedDescription,
+    /// Maximum number of triples with the resource or its blank nodes as object in the symmetric bounded description, further ones are left out.
+    pub max_inverse_triples: usize,
+    /// Seconds after which a query to the built-in SPARQL endpoint at `/sparql` is aborted.
+    pub sparql_timeout: u64,
+    /// Maximum number of solutions of a query to the built-in SPARQL endpoint, further ones are left out.
+    pub sparql_max_rows: usize,
+    /// shorten IRIs in JSON-LD output using a context with the configured namespaces
+    pub compact_jsonld: bool,
+    /// Check the local knowledge base files for modifications every this many seconds and reload them. Disabled if not set.
+    pub watch_interval: Option<u64>,
+    /// Bearer token that authorizes POST requests to `/admin/reload`. The endpoint is disabled if not set.
+    /// Serialized as `***`, so that neither `--print-config` nor the templates reveal it.
+    #[serde(serialize_with = "redact")]
+    pub admin_token: Option<String>,
+}
+
+/// Where the documents that describe a resource are served.
+/// Except for `none`, requests for a resource URI are redirected with 303 See Other to the document in the negotiated format, so that the URI identifies the thing itself and not a web page about it.
+/// See <https://www.w3.org/TR/cooluris/>.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentUrls {
+    /// describe the resource directly at its URI
+    None,
+    /// `X.html`, `X.ttl`, `X.nt`, `X.rdf` and `X.jsonld` for the resource `X`
+    Extension,
+    /// `page/X` for HTML and `data/X.ttl` and so on for the other formats, as in `DBpedia`
+    Path,
+}
+
+/// Which triples describe a resource in its RDF serializations, see <https://www.w3.org/submission/CBD/>.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundedDescription {
+    /// only the triples with the resource as subject
+    Subject,
+    /// concise bounded description, which also includes the triples of blank node objects, recursively
+    Cbd,
+    /// symmetric concise bounded description, which also includes the triples with the resource as object like the inverse properties on the HTML page
+    #[serde(rename = "scbd")]
+    Symmetric,
+}
+
+/// Serialize a secret as `***` if it is set.
+#[allow(clippy::ref_option)] // signature required by serialize_with
+fn redact<S: serde::Serializer>(secret: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    secret.as_ref().map(|_| "***").serialize(serializer)
 }
 
 mod iri_serde {
@@ -60,28 +138,102 @@ mod iri_serde {
     }
 }
 
+mod string_or_list {
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where D: Deserializer<'de> {
+        Ok(match StringOrList::deserialize(deserializer)? {
+            StringOrList::String(s) => vec![s],
+            StringOrList::List(v) => v,
+        })
+    }
+}
+
+/// Command line arguments, which take precedence over environment variables and the config file.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Config file that overrides the default configuration [default: data/config.toml if it exists]
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Knowledge base file, directory or URL, can be given multiple times
+    #[arg(short, long)]
+    pub kb_file: Vec<String>,
+    /// Port to listen on
+    #[arg(short, long)]
+    pub port: Option<u16>,
+    /// Address to listen on, can be given multiple times
+    #[arg(long)]
+    pub bind: Vec<String>,
+    /// Path prefix, for example /ontology
+    #[arg(long)]
+    pub base: Option<String>,
+    /// Print the effective configuration as JSON and exit
+    #[arg(long)]
+    pub print_config: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export the knowledge base as a static site instead of serving it
+    Export {
+        /// output directory
+        #[arg(default_value = "site")]
+        dir: PathBuf,
+    },
+}
+
+static ARGS: OnceLock<Args> = OnceLock::new();
+/// The command line arguments, which are ignored in tests because they are those of the test runner.
+pub fn args() -> &'static Args { ARGS.get_or_init(|| if cfg!(test) { Args::parse_from(["rickview"]) } else { Args::parse() }) }
+
 // path relative to source file
 static DEFAULT: &str = std::include_str!("../data/default.toml");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 impl Config {
     pub fn new() -> Result<Self, ConfigError> {
-        // configuration precedence: env var > config key > default value
+        // configuration precedence: command line argument > env var > config key > default value
         // namespaces cannot be configured with env vars
-        let mut config: Config = config::Config::builder()
+        let args = args();
+        // the default config file is optional but an explicitly given one has to exist
+        let (config_file, required) = match &args.config {
+            Some(path) => (path.as_path(), true),
+            None => (Path::new("data/config.toml"), false),
+        };
+        let mut builder = config::Config::builder()
             .add_source(File::from_str(DEFAULT, FileFormat::Toml))
-            .add_source(File::new("data/config.toml", FileFormat::Toml).required(false))
+            .add_source(File::from(config_file).format(FileFormat::Toml).required(required))
             .add_source(
                 Environment::with_prefix("rickview")
                     .try_parsing(true)
                     .list_separator(" ")
+                    .with_list_parse_key("kb_file")
+                    .with_list_parse_key("bind")
                     .with_list_parse_key("examples")
                     .with_list_parse_key("title_properties")
                     .with_list_parse_key("type_properties"),
             )
             .set_override("cargo_pkg_version", VERSION)?
-            .build()?
-            .try_deserialize()?;
+            .set_override_option("port", args.port)?
+            .set_override_option("base", args.base.clone())?;
+        if !args.kb_file.is_empty() {
+            builder = builder.set_override("kb_file", args.kb_file.clone())?;
+        }
+        if !args.bind.is_empty() {
+            builder = builder.set_override("bind", args.bind.clone())?;
+        }
+        let mut config: Config = builder.build()?.try_deserialize()?;
         if !config.base.is_empty() && !config.base.starts_with('/') {
             eprintln!("Warning: Non-empty base path '{}' does not start with a leading '/'.", config.base);
         }
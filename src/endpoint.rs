@@ -0,0 +1,356 @@
+//! Use a remote SPARQL endpoint instead of a local knowledge base, see [`EndpointGraph`].
+use log::{debug, error};
+use lru::LruCache;
+use serde_json::Value;
+use sophia::api::term::bnode_id::BnodeId;
+use sophia::api::term::matcher::TermMatcher;
+use sophia::api::term::{FromTerm, LanguageTag, SimpleTerm, Term, TermKind};
+use sophia::iri::IriRef;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use ureq::Agent;
+
+/// maximum number of cached query results
+static CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(1000).unwrap();
+/// cached query results older than this are fetched again
+static CACHE_TTL: Duration = Duration::from_secs(600);
+/// maximum number of triples fetched for a single triple pattern
+static LIMIT: usize = 10_000;
+static TIMEOUT: Duration = Duration::from_secs(30);
+
+type Triples = Arc<Vec<[SimpleTerm<'static>; 3]>>;
+
+/// Read-only graph that answers each triple pattern with a SPARQL SELECT query against a remote endpoint.
+/// Results are cached for a limited time, because rendering a single resource queries the same patterns multiple times.
+/// Blank nodes returned by the endpoint cannot be queried again, so their properties are not shown.
+pub struct EndpointGraph {
+    pub endpoint: String,
+    agent: Agent,
+    /// Query results with the time they were fetched by query string, least recently used first.
+    cache: Mutex<LruCache<String, (Instant, Triples)>>,
+    /// Cached query results older than this are removed and fetched again.
+    ttl: Duration,
+}
+
+impl EndpointGraph {
+    pub fn new(endpoint: String) -> Self { Self::with_cache(endpoint, CACHE_SIZE, CACHE_TTL) }
+
+    /// Endpoint graph that caches at most `size` query results for at most `ttl` each.
+    fn with_cache(endpoint: String, size: NonZeroUsize, ttl: Duration) -> Self {
+        let agent = Agent::config_builder().timeout_global(Some(TIMEOUT)).build().into();
+        EndpointGraph { endpoint, agent, cache: Mutex::new(LruCache::new(size)), ttl }
+    }
+
+    /// All triples matching the given pattern.
+    /// Errors are logged and result in no triples because the graph interface is infallible.
+    pub fn triples_matching<S, P, O>(&self, sm: S, pm: P, om: O) -> impl Iterator<Item = [SimpleTerm<'static>; 3]>
+    where
+        S: TermMatcher,
+        P: TermMatcher,
+        O: TermMatcher,
+    {
+        let constants = [constant(&sm), constant(&pm), constant(&om)];
+        let triples = match pattern(&constants) {
+            None => Arc::default(),
+            Some(pattern) => {
+                let query = format!("SELECT * WHERE {{ {pattern} }} LIMIT {LIMIT}");
+                self.query(&query, &constants).unwrap_or_else(|e| {
+                    error!("Error querying SPARQL endpoint {} with {query}: {e:?}", self.endpoint);
+                    Arc::default()
+                })
+            }
+        };
+        // the matchers may be more specific than the query, for example when they match one of multiple terms
+        (0..triples.len()).map(move |i| triples[i].clone()).filter(move |t| sm.matches(&t[0]) && pm.matches(&t[1]) && om.matches(&t[2]))
+    }
+
+    /// Cached results of a query for the given pattern, where the unbound positions are the variables s, p and o.
+    fn query(&self, query: &str, constants: &[Option<SimpleTerm<'static>>; 3]) -> anyhow::Result<Triples> {
+        {
+            let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            match cache.get(query) {
+                Some((t, triples)) if t.elapsed() < self.ttl => return Ok(triples.clone()),
+                Some(_) => {
+                    cache.pop(query);
+                }
+                None => {}
+            }
+        }
+        let t = Instant::now();
+        let body = self
+            .agent
+            .get(&self.endpoint)
+            .query("query", query)
+            .header("Accept", "application/sparql-results+json")
+            .call()?
+            .body_mut()
+            .read_to_string()?;
+        let json: Value = serde_json::from_str(&body)?;
+        let bindings = json["results"]["bindings"].as_array().ok_or_else(|| anyhow::anyhow!("Invalid SPARQL JSON result: {json}"))?;
+        let mut triples = Vec::with_capacity(bindings.len());
+        for binding in bindings {
+            let mut triple = Vec::with_capacity(3);
+            for (var, c) in ["s", "p", "o"].iter().zip(constants) {
+                match c {
+                    Some(c) => triple.push(c.clone()),
+                    None => triple.push(json_term(&binding[var]).ok_or_else(|| anyhow::anyhow!("Invalid binding for {var} in {binding}"))?),
+                }
+            }
+            triples.push(triple.try_into().expect("three terms"));
+        }
+        debug!("{} triples from SPARQL endpoint in {:?} for {query}", triples.len(), t.elapsed());
+        let triples = Arc::new(triples);
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        // expired results that have not been used since are removed first, the least recently used one is evicted if the cache is still full
+        while cache.peek_lru().is_some_and(|(_, (t, _))| t.elapsed() >= self.ttl) {
+            cache.pop_lru();
+        }
+        cache.put(query.to_owned(), (Instant::now(), triples.clone()));
+        Ok(triples)
+    }
+}
+
+fn constant<M: TermMatcher>(m: &M) -> Option<SimpleTerm<'static>> { m.constant().map(|c| SimpleTerm::from_term(c.as_simple())) }
+
+/// SPARQL triple pattern with variables s, p and o in the positions without constant.
+/// None if the pattern cannot be expressed, because blank nodes in SPARQL queries are variables.
+fn pattern(constants: &[Option<SimpleTerm<'static>>; 3]) -> Option<String> {
+    let mut terms = Vec::with_capacity(3);
+    for (var, c) in ["?s", "?p", "?o"].iter().zip(constants) {
+        terms.push(match c {
+            None => (*var).to_owned(),
+            Some(c) => sparql_term(c)?,
+        });
+    }
+    Some(terms.join(" "))
+}
+
+/// Term in SPARQL syntax, None for blank nodes, variables and quoted triples.
+fn sparql_term(term: &SimpleTerm<'_>) -> Option<String> {
+    match term.kind() {
+        TermKind::Iri => Some(format!("<{}>", term.iri()?.as_str())),
+        TermKind::Literal => {
+            let lex = term.lexical_form()?;
+            let escaped = lex.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "\\r");
+            match term.language_tag() {
+                Some(tag) => Some(format!("\"{escaped}\"@{}", tag.as_str())),
+                None => Some(format!("\"{escaped}\"^^<{}>", term.datatype()?.as_str())),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Convert an RDF term from the SPARQL 1.1 Query Results JSON Format.
+/// None for invalid IRIs, blank node labels and language tags, which the endpoint may return.
+fn json_term(v: &Value) -> Option<SimpleTerm<'static>> {
+    let value = v["value"].as_str()?.to_owned();
+    match v["type"].as_str()? {
+        "uri" => Some(SimpleTerm::Iri(IriRef::new(value.into()).ok()?)),
+        "bnode" => Some(SimpleTerm::BlankNode(BnodeId::new(value.into()).ok()?)),
+        // "typed-literal" is used by some older endpoints such as Virtuoso
+        "literal" | "typed-literal" => {
+            if let Some(lang) = v["xml:lang"].as_str() {
+                Some(SimpleTerm::LiteralLanguage(value.into(), LanguageTag::new(lang.to_owned().into()).ok()?))
+            } else {
+                let datatype = v["datatype"].as_str().unwrap_or("http://www.w3.org/2001/XMLSchema#string").to_owned();
+                Some(SimpleTerm::LiteralDatatype(value.into(), IriRef::new(datatype.into()).ok()?))
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sophia::api::term::matcher::Any;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    const EX: &str = "http://example.com/";
+
+    /// Local SPARQL endpoint that answers every query with the given JSON result and records the queries it received.
+    fn mock_endpoint(result: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/sparql", listener.local_addr().unwrap());
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let received = queries.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).unwrap();
+                // skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let target = request_line.split(' ').nth(1).unwrap();
+                let query = target.split_once("query=").map(|(_, q)| decode(q)).unwrap_or_default();
+                received.lock().unwrap().push(query);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/sparql-results+json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{result}",
+                    result.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, queries)
+    }
+
+    /// Decode a percent-encoded query parameter value.
+    fn decode(s: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut iter = s.bytes();
+        while let Some(b) = iter.next() {
+            match b {
+                b'%' => {
+                    let hex: String = iter.by_ref().take(2).map(char::from).collect();
+                    bytes.push(u8::from_str_radix(&hex, 16).unwrap());
+                }
+                b'+' => bytes.push(b' '),
+                _ => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn iri(suffix: &str) -> SimpleTerm<'static> { SimpleTerm::Iri(IriRef::new_unchecked(format!("{EX}{suffix}").into())) }
+
+    fn literal(lex: &str, lang: &str) -> SimpleTerm<'static> {
+        SimpleTerm::LiteralLanguage(lex.to_owned().into(), LanguageTag::new_unchecked(lang.to_owned().into()))
+    }
+
+    static SPO: &str = r#"{"head": {"vars": ["s", "p", "o"]}, "results": {"bindings": [
+        {"s": {"type": "uri", "value": "http://example.com/s"}, "p": {"type": "uri", "value": "http://example.com/p"}, "o": {"type": "literal", "value": "o", "xml:lang": "en"}}
+    ]}}"#;
+
+    #[test]
+    fn triple_patterns() {
+        let (url, queries) = mock_endpoint(SPO);
+        let g = EndpointGraph::new(url);
+        let (s, p, o) = (iri("s"), iri("p"), literal("o", "en"));
+        let expected = [s.clone(), p.clone(), o.clone()];
+        let patterns = [
+            ("?s ?p ?o", g.triples_matching(Any, Any, Any).collect::<Vec<_>>()),
+            ("<http://example.com/s> ?p ?o", g.triples_matching([&s], Any, Any).collect()),
+            ("?s <http://example.com/p> ?o", g.triples_matching(Any, [&p], Any).collect()),
+            ("?s ?p \"o\"@en", g.triples_matching(Any, Any, [&o]).collect()),
+            ("<http://example.com/s> <http://example.com/p> ?o", g.triples_matching([&s], [&p], Any).collect()),
+            ("<http://example.com/s> ?p \"o\"@en", g.triples_matching([&s], Any, [&o]).collect()),
+            ("?s <http://example.com/p> \"o\"@en", g.triples_matching(Any, [&p], [&o]).collect()),
+            ("<http://example.com/s> <http://example.com/p> \"o\"@en", g.triples_matching([&s], [&p], [&o]).collect()),
+        ];
+        let queries = queries.lock().unwrap();
+        assert_eq!(queries.len(), patterns.len());
+        for ((pattern, triples), query) in patterns.iter().zip(queries.iter()) {
+            assert_eq!(query, &format!("SELECT * WHERE {{ {pattern} }} LIMIT {LIMIT}"));
+            assert_eq!(triples.len(), 1, "{pattern}");
+            assert!(triples[0].iter().zip(&expected).all(|(a, b)| Term::eq(a, b)), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn matcher_filters_results() {
+        let (url, _) = mock_endpoint(SPO);
+        let g = EndpointGraph::new(url);
+        // matches no constant, so the query is for all triples, but the result doesn't match
+        let other = [iri("other"), iri("another")];
+        assert_eq!(g.triples_matching(&other[..], Any, Any).count(), 0);
+    }
+
+    #[test]
+    fn blank_node_pattern_is_not_queried() {
+        let (url, queries) = mock_endpoint(SPO);
+        let g = EndpointGraph::new(url);
+        let b = SimpleTerm::BlankNode(BnodeId::new_unchecked("b0".into()));
+        assert_eq!(g.triples_matching([&b], Any, Any).count(), 0);
+        assert!(queries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn escape_literals() {
+        let quoted =
+            SimpleTerm::LiteralDatatype("say \"hi\"\\\nnew line\r".into(), IriRef::new_unchecked("http://www.w3.org/2001/XMLSchema#string".into()));
+        assert_eq!(sparql_term(&quoted).unwrap(), r#""say \"hi\"\\\nnew line\r"^^<http://www.w3.org/2001/XMLSchema#string>"#);
+        assert_eq!(sparql_term(&literal("it's \"x\"", "en-GB")).unwrap(), r#""it's \"x\""@en-GB"#);
+        assert_eq!(sparql_term(&iri("a")).unwrap(), "<http://example.com/a>");
+        assert!(sparql_term(&SimpleTerm::BlankNode(BnodeId::new_unchecked("b".into()))).is_none());
+
+        let (url, queries) = mock_endpoint(SPO);
+        let g = EndpointGraph::new(url);
+        g.triples_matching(Any, Any, [&literal("a \"b\"\nc", "de")]).count();
+        assert_eq!(queries.lock().unwrap()[0], format!("SELECT * WHERE {{ ?s ?p \"a \\\"b\\\"\\nc\"@de }} LIMIT {LIMIT}"));
+    }
+
+    #[test]
+    fn parse_json_terms() {
+        let term = |json: &str| json_term(&serde_json::from_str(json).unwrap());
+        assert!(Term::eq(&term(r#"{"type": "uri", "value": "http://example.com/a"}"#).unwrap(), iri("a")));
+        assert_eq!(term(r#"{"type": "bnode", "value": "b1"}"#).unwrap().bnode_id().unwrap().as_str(), "b1");
+        assert!(Term::eq(&term(r#"{"type": "literal", "value": "x", "xml:lang": "en"}"#).unwrap(), literal("x", "en")));
+        let typed = term(r#"{"type": "typed-literal", "value": "1", "datatype": "http://www.w3.org/2001/XMLSchema#integer"}"#).unwrap();
+        assert_eq!(typed.lexical_form().unwrap(), "1");
+        assert_eq!(typed.datatype().unwrap().as_str(), "http://www.w3.org/2001/XMLSchema#integer");
+        let plain = term(r#"{"type": "literal", "value": "y"}"#).unwrap();
+        assert_eq!(plain.datatype().unwrap().as_str(), "http://www.w3.org/2001/XMLSchema#string");
+        assert!(term(r#"{"type": "triple", "value": "z"}"#).is_none());
+        assert!(term(r#"{"type": "uri"}"#).is_none());
+        assert!(term(r#"{"type": "uri", "value": "http://example.com/<script>"}"#).is_none());
+        assert!(term(r#"{"type": "literal", "value": "x", "xml:lang": "en'><script>"}"#).is_none());
+        assert!(term(r#"{"type": "literal", "value": "x", "datatype": "http://example.com/\"><script>"}"#).is_none());
+    }
+
+    #[test]
+    fn invalid_result() {
+        let (url, _) = mock_endpoint(r#"{"head": {"vars": ["s"]}, "results": {"bindings": [{"s": {"type": "unknown", "value": "a"}}]}}"#);
+        let g = EndpointGraph::new(url);
+        assert_eq!(g.triples_matching(Any, Any, Any).count(), 0);
+    }
+
+    #[test]
+    fn cache_hit() {
+        let (url, queries) = mock_endpoint(SPO);
+        let g = EndpointGraph::new(url);
+        let s = iri("s");
+        assert_eq!(g.triples_matching([&s], Any, Any).count(), 1);
+        assert_eq!(g.triples_matching([&s], Any, Any).count(), 1);
+        assert_eq!(queries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cache_expiry() {
+        let (url, queries) = mock_endpoint(SPO);
+        let g = EndpointGraph::with_cache(url, CACHE_SIZE, Duration::from_millis(50));
+        let s = iri("s");
+        g.triples_matching([&s], Any, Any).count();
+        std::thread::sleep(Duration::from_millis(100));
+        g.triples_matching([&s], Any, Any).count();
+        g.triples_matching([&s], Any, Any).count();
+        assert_eq!(queries.lock().unwrap().len(), 2);
+        // expired results are removed when another one is inserted
+        std::thread::sleep(Duration::from_millis(100));
+        g.triples_matching(Any, [&iri("p")], Any).count();
+        assert_eq!(g.cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cache_eviction() {
+        let (url, queries) = mock_endpoint(SPO);
+        let g = EndpointGraph::with_cache(url, NonZeroUsize::new(2).unwrap(), CACHE_TTL);
+        let (a, b, c) = (iri("a"), iri("b"), iri("c"));
+        g.triples_matching([&a], Any, Any).count();
+        g.triples_matching([&b], Any, Any).count();
+        // a is used more recently than b, so b is evicted for c
+        g.triples_matching([&a], Any, Any).count();
+        g.triples_matching([&c], Any, Any).count();
+        assert_eq!(queries.lock().unwrap().len(), 3);
+        g.triples_matching([&a], Any, Any).count();
+        assert_eq!(queries.lock().unwrap().len(), 3);
+        g.triples_matching([&b], Any, Any).count();
+        assert_eq!(queries.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,133 @@
+//! Export the knowledge base as a static site that can be served by any web server, see `rickview export`.
+use crate::about::About;
+use crate::config::config;
+use crate::rdf::{self, GraphEnum, SKOLEM_START};
+use crate::resource::Resource;
+use crate::{Context, FAVICON, Page, RICKVIEW_CSS, ROBOTO_CSS, ROBOTO300, TemplateConfig, add_hashes, data_path, template};
+use anyhow::{Context as _, bail};
+use log::{error, info, warn};
+use sophia::api::term::{SimpleTerm, Term};
+use sophia::api::term::matcher::Any;
+use sophia::iri::{Iri, IriRef};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// Write an HTML page and the RDF serializations of every subject in the namespace and every blank node as well as the index, about and class pages and the assets to the directory.
+/// The resource pages are named after their suffix with the extensions .html, .ttl, .nt, .rdf and .jsonld, so the server needs to map `X` to `X.html`, as for example GitHub Pages does.
+/// The site needs to be served at the configured base path. Search, SPARQL, fragments and loading further property values are not available, which the templates know from `static_site`.
+/// Resources that cannot be rendered or serialized are logged and left out, in which case an error is returned at the end.
+pub fn export(dir: &Path) -> anyhow::Result<()> {
+    let t = Instant::now();
+    let kb = rdf::load()?;
+    if matches!(kb.graph, GraphEnum::EndpointGraph(_)) {
+        bail!("Cannot export a SPARQL endpoint, disable endpoint_backend");
+    }
+    fs::create_dir_all(dir).with_context(|| format!("Cannot create output directory {}", dir.display()))?;
+    let write = |name: &str, contents: &[u8]| -> anyhow::Result<()> {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents).with_context(|| format!("Cannot write {}", path.display()))
+    };
+    write("rickview.css", RICKVIEW_CSS.as_bytes())?;
+    write("roboto.css", ROBOTO_CSS.as_bytes())?;
+    write("roboto300.woff2", ROBOTO300)?;
+    write("favicon.ico", FAVICON)?;
+
+    // GitHub Pages would otherwise not publish the blank node pages in .well-known
+    write(".nojekyll", b"")?;
+
+    let namespace = config().namespace.as_str();
+    // blank nodes are linked with their skolem IRIs, see https://www.w3.org/TR/rdf11-concepts/#section-skolemization
+    let skolem = |term: &SimpleTerm<'_>| term.bnode_id().map(|id| format!("{SKOLEM_START}{}", id.as_str()));
+    let mut suffixes = BTreeSet::<String>::new();
+    for [s, _, o] in kb.graph.triples_matching(Any, Any, Any).flatten() {
+        if let Some(suffix) = s.iri().and_then(|iri| iri.as_str().strip_prefix(namespace).map(str::to_owned)).or_else(|| skolem(&s)) {
+            suffixes.insert(suffix);
+        }
+        if config().show_inverse
+            && let Some(suffix) = skolem(&o)
+        {
+            suffixes.insert(suffix);
+        }
+    }
+    let mut has_index = false;
+    let mut count = 0;
+    let mut failures = 0;
+    for suffix in &suffixes {
+        if suffix.is_empty() {
+            has_index = true;
+        } else if suffix.contains(['?', '#', '\\']) || suffix.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") {
+            // files can only be served statically under paths that don't need query strings, fragments or relative segments
+            warn!("Skipping {suffix}, which cannot be a file name");
+            continue;
+        } else if PAGES.contains(&suffix.as_str()) {
+            warn!("Skipping {suffix}, whose page would be replaced by the {suffix} page of the site");
+            continue;
+        }
+        let name = if suffix.is_empty() { "index" } else { suffix };
+        let iri = config().namespace.resolve(IriRef::new_unchecked(suffix.as_str()));
+        let resource = rdf::resource(iri.as_ref(), true);
+        match template().render("resource", &context(None, None, Some(resource))) {
+            Ok(html) => write(&format!("{name}.html"), add_hashes(&html).as_bytes())?,
+            Err(e) => {
+                error!("Cannot render the page of {iri}: {e}");
+                failures += 1;
+            }
+        }
+        // where the download links point to, which is under data/ if document_urls is "path"
+        let base = &config().base;
+        let data = data_path(&format!("{base}/{name}"));
+        let data = data.strip_prefix(&format!("{base}/")).unwrap_or(&data);
+        for (extension, serialize) in SERIALIZATIONS {
+            match serialize(iri.as_ref()) {
+                Ok(serialization) => write(&format!("{data}.{extension}"), serialization.as_bytes())?,
+                Err(e) => {
+                    error!("Cannot serialize {iri} as .{extension}: {e}");
+                    failures += 1;
+                }
+            }
+        }
+        count += 1;
+        if count % 1000 == 0 {
+            info!("Exported {count} of {} resources", suffixes.len());
+        }
+    }
+    if !has_index {
+        let html = template().render("index", &context(None, None, None))?;
+        write("index.html", add_hashes(&html).as_bytes())?;
+    }
+    let html = template().render("about", &context(Some(About::new()), None, None))?;
+    write("about.html", add_hashes(&html).as_bytes())?;
+    let page = Page { title: "Classes".to_owned(), body: crate::classes::class_tree() };
+    let html = template().render("custom", &context(None, Some(page), None))?;
+    write("classes.html", add_hashes(&html).as_bytes())?;
+    info!("Exported {count} resources to {} in {:?}", dir.display(), t.elapsed());
+    if failures > 0 {
+        bail!("{failures} pages and serializations could not be exported, see the errors above");
+    }
+    Ok(())
+}
+
+/// Names of the pages of the site besides those of the resources.
+const PAGES: [&str; 3] = ["about", "classes", "index"];
+
+/// Template context of a page of the static site.
+fn context(about: Option<About>, page: Option<Page>, resource: Option<Resource>) -> Context {
+    Context { config: TemplateConfig::new(true), about, resource, page }
+}
+
+type Serialize = fn(Iri<&str>) -> Result<String, Box<dyn std::error::Error>>;
+
+/// File extensions of the serializations of a resource.
+const SERIALIZATIONS: &[(&str, Serialize)] = &[
+    ("ttl", rdf::serialize_turtle),
+    ("nt", rdf::serialize_nt),
+    #[cfg(feature = "rdfxml")]
+    ("rdf", rdf::serialize_rdfxml),
+    #[cfg(feature = "jsonld")]
+    ("jsonld", rdf::serialize_jsonld),
+];
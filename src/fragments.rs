@@ -0,0 +1,292 @@
+//! Triple Pattern Fragments interface, so that Linked Data Fragments clients like Comunica can query the knowledge base.
+//! See <https://linkeddatafragments.org/specification/triple-pattern-fragments/>.
+use crate::rdf::{encode_template_value, kb, turtle_config};
+use serde::Deserialize;
+use sophia::api::dataset::MutableDataset;
+use sophia::api::graph::MutableGraph;
+use sophia::api::prefix::Prefix;
+use sophia::api::serializer::{QuadSerializer, Stringifier, TripleSerializer};
+use sophia::api::term::matcher::TermMatcher;
+use sophia::api::term::{LanguageTag, SimpleTerm, Term};
+use sophia::inmem::dataset::FastDataset;
+use sophia::inmem::graph::FastGraph;
+use sophia::iri::{Iri, IriRef};
+use sophia::turtle::serializer::trig::TrigSerializer;
+use sophia::turtle::serializer::turtle::{TurtleConfig, TurtleSerializer};
+
+pub const TURTLE: &str = "text/turtle";
+pub const TRIG: &str = "application/trig";
+/// number of triples per page
+const PAGE_SIZE: usize = 100;
+const HYDRA: &str = "http://www.w3.org/ns/hydra/core#";
+const VOID: &str = "http://rdfs.org/ns/void#";
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+const XSD_INTEGER: &str = "http://www.w3.org/2001/XMLSchema#integer";
+const FOAF_PRIMARY_TOPIC: &str = "http://xmlns.com/foaf/0.1/primaryTopic";
+
+/// Why a fragment could not be served.
+#[derive(Debug)]
+pub enum FragmentError {
+    /// invalid term in the selector
+    BadRequest(String),
+    Internal(String),
+}
+
+/// Triple pattern and page of a fragment request, where missing positions and variables match any term.
+/// Terms use the Hydra explicit representation, for example `http://example.com/X`, `"a"@en` or `"1"^^http://www.w3.org/2001/XMLSchema#integer`.
+#[derive(Deserialize)]
+pub struct Selector {
+    subject: Option<String>,
+    predicate: Option<String>,
+    object: Option<String>,
+    page: Option<usize>,
+}
+
+/// Matches the term if there is one and otherwise everything.
+struct Position(Option<SimpleTerm<'static>>);
+
+impl TermMatcher for Position {
+    type Term = SimpleTerm<'static>;
+
+    fn matches<T2: Term + ?Sized>(&self, term: &T2) -> bool { self.0.as_ref().is_none_or(|t| Term::eq(t, term.borrow_term())) }
+
+    fn constant(&self) -> Option<&Self::Term> { self.0.as_ref() }
+}
+
+/// Parse a term in Hydra explicit representation, None for variables and blank nodes, which act as variables.
+fn parse_term(s: &str) -> Result<Option<SimpleTerm<'static>>, String> {
+    if s.is_empty() || s.starts_with('?') || s.starts_with("_:") {
+        return Ok(None);
+    }
+    if let Some(rest) = s.strip_prefix('"') {
+        let (value, suffix) = rest.rsplit_once('"').ok_or_else(|| format!("Unterminated literal {s}"))?;
+        let value = value.to_owned().into();
+        return if let Some(lang) = suffix.strip_prefix('@') {
+            let tag = LanguageTag::new(lang.to_owned().into()).map_err(|e| format!("Invalid language tag in {s}: {e}"))?;
+            Ok(Some(SimpleTerm::LiteralLanguage(value, tag)))
+        } else if let Some(datatype) = suffix.strip_prefix("^^") {
+            let datatype = datatype.trim_start_matches('<').trim_end_matches('>');
+            Iri::new(datatype).map_err(|e| format!("Invalid datatype in {s}: {e}"))?;
+            Ok(Some(SimpleTerm::LiteralDatatype(value, IriRef::new_unchecked(datatype.to_owned().into()))))
+        } else if suffix.is_empty() {
+            Ok(Some(SimpleTerm::LiteralDatatype(value, IriRef::new_unchecked(XSD_STRING.into()))))
+        } else {
+            Err(format!("Invalid literal {s}"))
+        };
+    }
+    let iri = s.trim_start_matches('<').trim_end_matches('>');
+    Iri::new(iri.to_owned()).map_err(|e| format!("Invalid IRI {s}: {e}"))?;
+    Ok(Some(SimpleTerm::Iri(IriRef::new_unchecked(iri.to_owned().into()))))
+}
+
+fn iri(s: &str) -> SimpleTerm<'static> { SimpleTerm::Iri(IriRef::new_unchecked(s.to_owned().into())) }
+
+fn literal(value: String, datatype: &str) -> SimpleTerm<'static> {
+    SimpleTerm::LiteralDatatype(value.into(), IriRef::new_unchecked(datatype.to_owned().into()))
+}
+
+/// The configured prefixes together with those of the Hydra and `VoID` vocabularies.
+fn config() -> TurtleConfig {
+    let config = turtle_config();
+    let mut prefixes = config.prefix_map().to_vec();
+    prefixes.push((Prefix::new_unchecked("hydra".into()), Iri::new_unchecked(HYDRA.into())));
+    prefixes.push((Prefix::new_unchecked("void".into()), Iri::new_unchecked(VOID.into())));
+    config.with_own_prefix_map(prefixes)
+}
+
+impl Selector {
+    /// URL of this fragment with only the bound positions and the page, if any, as parameters.
+    /// The values are encoded like clients expand the URI template of the search form, so that requested fragments are identified by the URL that was requested.
+    fn url(&self, dataset_url: &str, page: Option<usize>) -> String {
+        let mut params: Vec<String> = [("subject", &self.subject), ("predicate", &self.predicate), ("object", &self.object)]
+            .into_iter()
+            .filter_map(|(name, value)| value.as_ref().filter(|v| !v.is_empty()).map(|v| format!("{name}={}", encode_template_value(v))))
+            .collect();
+        params.extend(page.map(|page| format!("page={page}")));
+        if params.is_empty() { dataset_url.to_owned() } else { format!("{dataset_url}?{}", params.join("&")) }
+    }
+}
+
+/// Serialize a page of the triples matching the selector together with the Hydra metadata and controls as Turtle or `TriG`.
+/// In `TriG`, the metadata is in a separate graph, as recommended by the specification, while Turtle contains everything in the default graph.
+/// The dataset URL is the address of the fragments interface.
+pub fn fragment(selector: &Selector, dataset_url: &str, media_type: &str) -> Result<String, FragmentError> {
+    let term = |t: &Option<String>| parse_term(t.as_deref().unwrap_or_default()).map_err(FragmentError::BadRequest);
+    let pattern = (term(&selector.subject)?, term(&selector.predicate)?, term(&selector.object)?);
+    let page = selector.page.unwrap_or(1).max(1);
+    let start = (page - 1) * PAGE_SIZE;
+    let end = start + PAGE_SIZE;
+
+    let kb = kb();
+    let unbound = pattern.0.is_none() && pattern.1.is_none() && pattern.2.is_none();
+    let mut triples = Vec::with_capacity(PAGE_SIZE);
+    let mut count = 0;
+    for triple in kb.graph.triples_matching(Position(pattern.0), Position(pattern.1), Position(pattern.2)).flatten() {
+        if count >= start && count < end {
+            triples.push(triple);
+        }
+        count += 1;
+        // stop one after the end of the page, which is enough to know whether there is a next page
+        if count > end {
+            break;
+        }
+    }
+    let has_next = count > end;
+    // Counting all matches is too expensive for large fragments, so the total is only an estimate unless the last page was reached:
+    // the size of the knowledge base for the unbound pattern and otherwise the triples up to the next page.
+    let total = if has_next && unbound { kb.sources.iter().map(|(_, n)| n).sum::<usize>().max(count) } else { count };
+
+    let fragment_url = selector.url(dataset_url, selector.page);
+    let fragment = iri(&fragment_url);
+    let dataset = iri(&format!("{dataset_url}#dataset"));
+    let hydra = |name: &str| iri(&format!("{HYDRA}{name}"));
+    let void = |name: &str| iri(&format!("{VOID}{name}"));
+    let mut meta: Vec<[SimpleTerm<'static>; 3]> = vec![
+        [dataset.clone(), iri(&format!("{RDF}type")), void("Dataset")],
+        [dataset.clone(), iri(&format!("{RDF}type")), hydra("Collection")],
+        [dataset.clone(), void("subset"), fragment.clone()],
+        [fragment.clone(), iri(&format!("{RDF}type")), hydra("PartialCollectionView")],
+        [fragment.clone(), void("triples"), literal(total.to_string(), XSD_INTEGER)],
+        [fragment.clone(), hydra("totalItems"), literal(total.to_string(), XSD_INTEGER)],
+        [fragment.clone(), hydra("itemsPerPage"), literal(PAGE_SIZE.to_string(), XSD_INTEGER)],
+        [fragment.clone(), hydra("first"), iri(&selector.url(dataset_url, Some(1)))],
+    ];
+    if page > 1 {
+        meta.push([fragment.clone(), hydra("previous"), iri(&selector.url(dataset_url, Some(page - 1)))]);
+    }
+    if has_next {
+        meta.push([fragment.clone(), hydra("next"), iri(&selector.url(dataset_url, Some(page + 1)))]);
+    }
+    // search form that tells clients how to request other fragments, identified by IRIs so that they cannot collide with blank nodes of the data
+    let search = iri(&format!("{dataset_url}#search"));
+    meta.push([dataset.clone(), hydra("search"), search.clone()]);
+    meta.push([search.clone(), hydra("template"), literal(format!("{dataset_url}{{?subject,predicate,object}}"), XSD_STRING)]);
+    meta.push([search.clone(), hydra("variableRepresentation"), hydra("ExplicitRepresentation")]);
+    for variable in ["subject", "predicate", "object"] {
+        let mapping = iri(&format!("{dataset_url}#{variable}"));
+        meta.push([search.clone(), hydra("mapping"), mapping.clone()]);
+        meta.push([mapping.clone(), hydra("variable"), literal(variable.to_owned(), XSD_STRING)]);
+        meta.push([mapping, hydra("property"), iri(&format!("{RDF}{variable}"))]);
+    }
+
+    let result = if media_type == TRIG {
+        let mut quads = FastDataset::new();
+        let metadata = iri(&format!("{fragment_url}#metadata"));
+        meta.push([metadata.clone(), iri(FOAF_PRIMARY_TOPIC), fragment]);
+        for [s, p, o] in &triples {
+            quads.insert(s, p, o, None as Option<&SimpleTerm<'_>>).map_err(|e| FragmentError::Internal(e.to_string()))?;
+        }
+        for [s, p, o] in &meta {
+            quads.insert(s, p, o, Some(&metadata)).map_err(|e| FragmentError::Internal(e.to_string()))?;
+        }
+        TrigSerializer::new_stringifier_with_config(config()).serialize_dataset(&quads).map(|s| s.to_string())
+    } else {
+        let mut graph = FastGraph::new();
+        for [s, p, o] in triples.iter().chain(&meta) {
+            graph.insert(s, p, o).map_err(|e| FragmentError::Internal(e.to_string()))?;
+        }
+        TurtleSerializer::new_stringifier_with_config(config()).serialize_graph(&graph).map(|s| s.to_string())
+    };
+    result.map_err(|e| FragmentError::Internal(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rdf::tests::{MEMBERS, iri as resource, test_kb};
+    use sophia::api::graph::Graph;
+    use sophia::api::term::matcher::Any;
+    use sophia::turtle::parser::turtle;
+
+    const DATASET: &str = "http://localhost/fragments";
+
+    fn selector(subject: &str, predicate: &str, object: &str, page: Option<usize>) -> Selector {
+        let position = |s: &str| Some(s.to_owned()).filter(|s| !s.is_empty());
+        Selector { subject: position(subject), predicate: position(predicate), object: position(object), page }
+    }
+
+    /// Parsed Turtle fragment.
+    fn fragment_graph(selector: &Selector) -> FastGraph {
+        let body = fragment(selector, DATASET, TURTLE).unwrap();
+        let mut graph = FastGraph::new();
+        graph.insert_all(turtle::parse_str(&body)).unwrap();
+        graph
+    }
+
+    /// Object of the Hydra control or metadata property of the fragment, if any.
+    fn hydra_value(graph: &FastGraph, fragment_url: &str, name: &str) -> Option<String> {
+        let [_, _, o] = graph.triples_matching([iri(fragment_url)], [iri(&format!("{HYDRA}{name}"))], Any).next()?.unwrap();
+        Some(o.iri().map_or_else(|| o.lexical_form().unwrap().to_string(), |i| i.as_str().to_owned()))
+    }
+
+    #[test]
+    fn parse_terms() {
+        assert!(parse_term("").unwrap().is_none());
+        assert!(parse_term("?s").unwrap().is_none());
+        assert!(parse_term("_:b0").unwrap().is_none());
+        assert_eq!(parse_term("http://example.com/X").unwrap(), Some(iri("http://example.com/X")));
+        assert_eq!(parse_term("<http://example.com/X>").unwrap(), Some(iri("http://example.com/X")));
+        assert_eq!(parse_term("\"a\"").unwrap(), Some(literal("a".to_owned(), XSD_STRING)));
+        // the last quote ends the value, so it may contain quotes
+        assert_eq!(parse_term("\"say \"hi\"\"").unwrap(), Some(literal("say \"hi\"".to_owned(), XSD_STRING)));
+        assert_eq!(parse_term("\"a\"@en-GB").unwrap(), Some(SimpleTerm::LiteralLanguage("a".into(), LanguageTag::new_unchecked("en-GB".into()))));
+        assert_eq!(parse_term("\"1\"^^http://www.w3.org/2001/XMLSchema#integer").unwrap(), Some(literal("1".to_owned(), XSD_INTEGER)));
+        assert_eq!(parse_term("\"1\"^^<http://www.w3.org/2001/XMLSchema#integer>").unwrap(), Some(literal("1".to_owned(), XSD_INTEGER)));
+        for invalid in ["\"unterminated", "\"", "\"a\"@", "\"a\"@en gb", "\"a\"^^", "\"a\"^^not an iri", "\"a\"x", "not an iri"] {
+            assert!(parse_term(invalid).is_err(), "{invalid} should be invalid");
+        }
+    }
+
+    #[test]
+    fn pages() {
+        test_kb();
+        let (subject, predicate) = (resource("Many"), resource("member"));
+        let url = |page: usize| format!("{DATASET}?subject={}&predicate={}&page={page}", encode_template_value(&subject), encode_template_value(&predicate));
+        let members = |graph: &FastGraph| graph.triples_matching(Any, [iri(&predicate)], Any).count();
+
+        // without a page, the fragment is the first page, identified by the requested URL
+        let first = fragment_graph(&selector(&subject, &predicate, "", None));
+        let first_url = format!("{DATASET}?subject={}&predicate={}", encode_template_value(&subject), encode_template_value(&predicate));
+        assert_eq!(members(&first), PAGE_SIZE);
+        assert_eq!(hydra_value(&first, &first_url, "first"), Some(url(1)));
+        assert_eq!(hydra_value(&first, &first_url, "next"), Some(url(2)));
+        assert_eq!(hydra_value(&first, &first_url, "previous"), None);
+        // counting stops after the page, so the total is a lower bound until the last page is reached
+        assert_eq!(hydra_value(&first, &first_url, "totalItems"), Some((PAGE_SIZE + 1).to_string()));
+
+        let last = fragment_graph(&selector(&subject, &predicate, "", Some(2)));
+        assert_eq!(members(&last), MEMBERS - PAGE_SIZE);
+        assert_eq!(hydra_value(&last, &url(2), "previous"), Some(url(1)));
+        assert_eq!(hydra_value(&last, &url(2), "next"), None);
+        assert_eq!(hydra_value(&last, &url(2), "totalItems"), Some(MEMBERS.to_string()));
+
+        let beyond = fragment_graph(&selector(&subject, &predicate, "", Some(3)));
+        assert_eq!(members(&beyond), 0);
+        assert_eq!(hydra_value(&beyond, &url(3), "next"), None);
+        assert_eq!(hydra_value(&beyond, &url(3), "totalItems"), Some(MEMBERS.to_string()));
+
+        // page 0 is treated as the first page but still identified by the requested URL
+        let zero = fragment_graph(&selector(&subject, &predicate, "", Some(0)));
+        assert_eq!(members(&zero), PAGE_SIZE);
+        assert_eq!(hydra_value(&zero, &url(0), "next"), Some(url(2)));
+    }
+
+    #[test]
+    fn total_of_exact_pattern() {
+        test_kb();
+        let (subject, predicate, object) = (resource("Many"), resource("member"), resource("m7"));
+        let graph = fragment_graph(&selector(&subject, &predicate, &object, None));
+        let url = format!(
+            "{DATASET}?subject={}&predicate={}&object={}",
+            encode_template_value(&subject),
+            encode_template_value(&predicate),
+            encode_template_value(&object)
+        );
+        assert_eq!(hydra_value(&graph, &url, "totalItems"), Some("1".to_owned()));
+        assert_eq!(hydra_value(&graph, &url, "next"), None);
+        let missing = fragment_graph(&selector(&subject, &predicate, "\"m7\"", None));
+        let url = format!("{DATASET}?subject={}&predicate={}&object=%22m7%22", encode_template_value(&subject), encode_template_value(&predicate));
+        assert_eq!(hydra_value(&missing, &url, "totalItems"), Some("0".to_owned()));
+    }
+}
@@ -0,0 +1,93 @@
+//! Persist the title and type indexes between restarts, see `index_cache` in the config.
+use crate::config::config;
+use crate::watch::{Fingerprint, fingerprint};
+use anyhow::Context;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::time::Instant;
+
+/// Maps RDF resource URIs to titles and resource suffixes to type URIs, see `Kb::titles` and `Kb::types`.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Indexes {
+    pub titles: HashMap<String, String>,
+    pub types: HashMap<String, String>,
+}
+
+/// Everything the indexes depend on, so that a cache file is only used if none of it changed.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Key {
+    files: Fingerprint,
+    namespace: String,
+    title_properties: Vec<String>,
+    type_properties: Vec<String>,
+    langs: Vec<String>,
+}
+
+impl Key {
+    /// The key for the current knowledge base files, None if caching is disabled or there are remote or no knowledge base files.
+    /// Create it before loading the knowledge base so that files that change while loading invalidate the cache.
+    pub fn new() -> Option<Key> {
+        let c = config();
+        if c.index_cache.is_none() || c.endpoint_backend || c.kb_file.is_empty() || c.kb_file.iter().any(|f| f.starts_with("http")) {
+            return None;
+        }
+        Some(Key {
+            files: fingerprint(),
+            namespace: c.namespace.as_str().to_owned(),
+            title_properties: c.title_properties.clone(),
+            type_properties: c.type_properties.clone(),
+            langs: c.langs.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile<K, I> {
+    key: K,
+    indexes: I,
+}
+
+/// Load the indexes from the cache file if it exists and was created for the same key.
+pub fn load(key: &Key) -> Option<Indexes> {
+    let filename = config().index_cache.as_ref()?;
+    let t = Instant::now();
+    let file = File::open(filename).ok()?;
+    let cache: CacheFile<Key, Indexes> =
+        match zstd::Decoder::new(file).map_err(anyhow::Error::from).and_then(|d| Ok(serde_json::from_reader(BufReader::new(d))?)) {
+            Ok(cache) => cache,
+            Err(e) => {
+                warn!("Ignoring invalid index cache file {filename}: {e}");
+                return None;
+            }
+        };
+    if &cache.key != key {
+        debug!("Index cache {filename} is outdated");
+        return None;
+    }
+    info!("Loaded {} titles and {} types from index cache {filename} in {:?}", cache.indexes.titles.len(), cache.indexes.types.len(), t.elapsed());
+    Some(cache.indexes)
+}
+
+/// Write the indexes to the cache file, replacing it at once so that readers never see a partial file.
+/// Errors are only logged because the cache is optional.
+pub fn save(key: &Key, indexes: &Indexes) {
+    let Some(filename) = &config().index_cache else {
+        return;
+    };
+    let tmp = format!("{filename}.tmp");
+    let result = (|| -> anyhow::Result<()> {
+        let file = File::create(&tmp).with_context(|| format!("Cannot create {tmp}"))?;
+        let mut encoder = zstd::Encoder::new(BufWriter::new(file), 0)?;
+        serde_json::to_writer(&mut encoder, &CacheFile { key, indexes })?;
+        encoder.finish()?.flush()?;
+        std::fs::rename(&tmp, filename)?;
+        Ok(())
+    })();
+    match result {
+        Ok(()) => info!("Saved title and type indexes to {filename}"),
+        Err(e) => warn!("Could not save index cache {filename}: {e:?}"),
+    }
+}
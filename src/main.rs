@@ -7,36 +7,57 @@
 #![deny(rust_2018_idioms)]
 //! Lightweight and performant RDF browser.
 //! An RDF browser is a web application that *resolves* RDF resources: given the HTTP(s) URL identifying a resource it returns an HTML summary.
-//! Besides HTML, the RDF serialization formats RDF/XML, Turtle and N-Triples are also available using content negotiation.
+//! Besides HTML, the RDF serialization formats RDF/XML, Turtle, N-Triples and JSON-LD are also available using content negotiation.
+//! The knowledge base can also be queried at the built-in SPARQL endpoint `/sparql`.
 //! Default configuration is stored in `data/default.toml`, which can be overriden in `data/config.toml` or environment variables.
 //! Configuration keys are in `lower_snake_case`, while environment variables are prefixed with RICKVIEW\_ and are `in SCREAMING_SNAKE_CASE`.
 mod about;
 mod classes;
 /// The main module uses Actix Web to serve resources as HTML and other formats.
 mod config;
+mod endpoint;
+mod export;
+mod fragments;
+mod index_cache;
+mod metrics;
+mod negotiate;
 mod rdf;
 mod resource;
+mod search;
+#[cfg(feature = "sparql")]
+mod sparql;
+mod watch;
 
-use crate::config::{Config, config};
+use crate::config::{Config, DocumentUrls, config};
 use crate::resource::Resource;
 use about::About;
-use actix_web::body::MessageBody;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
 use actix_web::http::header::{self, ETag, EntityTag};
-use actix_web::middleware::Compress;
+use actix_web::middleware::{Compress, Next, from_fn};
 use actix_web::web::scope;
-use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, get, head, web};
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, post, route, web};
+use anyhow::{Context as _, bail};
 use const_fnv1a_hash::{fnv1a_hash_32, fnv1a_hash_str_32};
 use log::{debug, error, info, trace, warn};
+use negotiate::{Format, negotiate, negotiate_media_type};
 use serde::{Deserialize, Serialize};
 use sophia::iri::IriRef;
-use std::error::Error;
-use std::sync::LazyLock;
+use std::cell::RefCell;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
 use tinytemplate::TinyTemplate;
+use yoke::{Yoke, Yokeable};
 
 static HEADER: &str = std::include_str!("../data/header.html");
 static FOOTER: &str = std::include_str!("../data/footer.html");
+static SEARCH: &str = std::include_str!("../data/search.html");
 static RESOURCE: &str = std::include_str!("../data/resource.html");
 static FAVICON: &[u8; 318] = std::include_bytes!("../data/favicon.ico");
 // extremely low risk of collision, worst case is out of date favicon or CSS
@@ -50,6 +71,8 @@ static INDEX: &str = std::include_str!("../data/index.html");
 static ABOUT: &str = std::include_str!("../data/about.html");
 static CUSTOM: &str = std::include_str!("../data/custom.html");
 static RUN_ID: AtomicU32 = AtomicU32::new(0);
+/// Responses whose content is negotiated vary by the Accept header.
+const VARY: (header::HeaderName, &str) = (header::VARY, "Accept");
 
 // 8 chars hexadecimal, not worth it to add base64 dependency to save 2 chars
 static FAVICON_SHASH: LazyLock<String> = LazyLock::new(|| format!("{FAVICON_HASH:x}"));
@@ -66,29 +89,122 @@ struct Page {
     body: String,
 }
 
+/// The configuration as the templates see it, together with whether they are rendered for a static site without a server, see [`export`].
 #[derive(Serialize)]
-struct Context {
+struct TemplateConfig {
+    #[serde(flatten)]
     config: &'static Config,
+    static_site: bool,
+    /// whether RDF/XML can be served, which depends on the compiled features
+    rdfxml: bool,
+    /// whether JSON-LD can be served, which depends on the compiled features
+    jsonld: bool,
+}
+
+impl TemplateConfig {
+    fn new(static_site: bool) -> Self {
+        TemplateConfig { config: config(), static_site, rdfxml: cfg!(feature = "rdfxml"), jsonld: cfg!(feature = "jsonld") }
+    }
+}
+
+#[derive(Serialize)]
+struct Context {
+    config: TemplateConfig,
     about: Option<About>,
     resource: Option<Resource>,
     page: Option<Page>,
 }
 
-fn template() -> TinyTemplate<'static> {
+/// Names of the templates with their built-in contents.
+static BUILTIN_TEMPLATES: [(&str, &str); 7] =
+    [("header", HEADER), ("search", SEARCH), ("footer", FOOTER), ("resource", RESOURCE), ("index", INDEX), ("about", ABOUT), ("custom", CUSTOM)];
+/// Names of the templates with their sources.
+type Sources = Arc<[(&'static str, String)]>;
+/// Templates in use, see [`set_templates`].
+static TEMPLATES: LazyLock<RwLock<Sources>> =
+    LazyLock::new(|| RwLock::new(BUILTIN_TEMPLATES.iter().map(|(name, template)| (*name, (*template).to_owned())).collect()));
+
+/// Templates compiled from the sources they borrow.
+#[derive(Yokeable)]
+struct Compiled<'a>(TinyTemplate<'a>);
+
+/// Compiled templates that own their sources, so that those are freed when no thread uses them anymore.
+struct Templates(Yoke<Compiled<'static>, Sources>);
+
+impl Templates {
+    fn render<C: Serialize>(&self, name: &str, context: &C) -> tinytemplate::error::Result<String> { self.0.get().0.render(name, context) }
+}
+
+thread_local! {
+    /// Compiled templates of this thread, because `TinyTemplate` cannot be shared between threads.
+    static COMPILED_TEMPLATES: RefCell<Option<Rc<Templates>>> = const { RefCell::new(None) };
+}
+
+/// Read the templates from the files `header.html`, `search.html`, `footer.html`, `resource.html`, `index.html`, `about.html` and `custom.html` in the configured `templates` directory and check that they can be parsed.
+/// Built-in templates are used for missing files.
+fn load_templates() -> anyhow::Result<Vec<(&'static str, String)>> {
+    let mut templates: Vec<(&str, String)> = BUILTIN_TEMPLATES.iter().map(|(name, template)| (*name, (*template).to_owned())).collect();
+    let Some(dir) = &config().templates else {
+        return Ok(templates);
+    };
+    let dir = Path::new(dir);
+    if !dir.is_dir() {
+        bail!("Template directory {} does not exist", dir.display());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        if path.extension().is_some_and(|e| e == "html") && !BUILTIN_TEMPLATES.iter().any(|(n, _)| *n == name) {
+            warn!("Ignoring {}, which does not replace a template", path.display());
+        }
+    }
+    for (name, template) in &mut templates {
+        let path = dir.join(format!("{name}.html"));
+        match fs::read_to_string(&path) {
+            Ok(s) => {
+                info!("Using template {}", path.display());
+                *template = s;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => debug!("{} does not exist, using the built-in {name} template", path.display()),
+            Err(e) => return Err(e).with_context(|| format!("Cannot read template {}", path.display())),
+        }
+    }
+    let mut tt = TinyTemplate::new();
+    for (name, template) in &templates {
+        tt.add_template(name, template).with_context(|| format!("Cannot parse {name} template"))?;
+    }
+    Ok(templates)
+}
+
+/// Use the given templates from now on.
+/// Threads compile them again on their next use and then drop the previous ones.
+fn set_templates(templates: Vec<(&'static str, String)>) { *TEMPLATES.write().unwrap_or_else(PoisonError::into_inner) = templates.into(); }
+
+/// The compiled templates, which are only parsed again when they have been replaced.
+fn template() -> Rc<Templates> {
+    let sources = TEMPLATES.read().unwrap_or_else(PoisonError::into_inner).clone();
+    COMPILED_TEMPLATES.with_borrow_mut(|compiled| {
+        if let Some(templates) = compiled
+            && Arc::ptr_eq(templates.0.backing_cart(), &sources)
+        {
+            return templates.clone();
+        }
+        let templates = Rc::new(Templates(Yoke::attach_to_cart(sources, |sources| Compiled(compile_templates(sources)))));
+        *compiled = Some(templates.clone());
+        templates
+    })
+}
+
+fn compile_templates<'a>(templates: &'a [(&'static str, String)]) -> TinyTemplate<'a> {
     let mut tt = TinyTemplate::new();
-    tt.add_template("header", HEADER).expect("Could not parse header template");
-    tt.add_template("footer", FOOTER).expect("Could not parse footer template");
-    tt.add_template("resource", RESOURCE).expect("Could not parse resource page template");
-    tt.add_template("index", INDEX).expect("Could not parse index page template");
-    tt.add_template("about", ABOUT).expect("Could not parse about page template");
-    tt.add_template("custom", CUSTOM).expect("Could not parse about page template");
+    for (name, template) in templates {
+        tt.add_template(name, template).unwrap_or_else(|e| panic!("Could not parse {name} template: {e}"));
+    }
+    // last segment after '/' or '#', the whole URI if there is none
     tt.add_formatter("uri_to_suffix", |json, output| {
-        let s = json.as_str().unwrap_or_else(|| panic!("JSON value is not a string: {json}"));
-        let mut s = s.rsplit_once('/').unwrap_or_else(|| panic!("no '/' in URI '{s}'")).1;
-        if s.contains('#') {
-            s = s.rsplit_once('#').unwrap().1;
-        }
-        output.push_str(s);
+        let s = json.as_str().ok_or_else(|| tinytemplate::error::Error::GenericError { msg: format!("JSON value is not a string: {json}") })?;
+        let s = s.rsplit(['/', '#']).next().filter(|suffix| !suffix.is_empty()).unwrap_or(s);
+        tinytemplate::escape(s, output);
         Ok(())
     });
     tt
@@ -107,17 +223,17 @@ where &'static T: MessageBody {
 }
 
 // For maximum robustness, serve CSS, font and icon from any path. Collision with RDF resource URIs unlikely.
-#[get("{_anypath:.*/|}rickview.css")]
+#[route("{_anypath:.*/|}rickview.css", method = "GET", method = "HEAD")]
 async fn rickview_css(r: HttpRequest) -> impl Responder { hash_etag(&r, RICKVIEW_CSS, &RICKVIEW_CSS_SHASH, &RICKVIEW_CSS_SHASH_QUOTED, "text/css") }
 
-#[get("{_anypath:.*/|}roboto.css")]
+#[route("{_anypath:.*/|}roboto.css", method = "GET", method = "HEAD")]
 async fn roboto_css(r: HttpRequest) -> impl Responder { hash_etag(&r, ROBOTO_CSS, &ROBOTO_CSS_SHASH, &ROBOTO_CSS_SHASH_QUOTED, "text/css") }
 
 // cached automatically by browser
-#[get("{_anypath:.*/|}roboto300.woff2")]
+#[route("{_anypath:.*/|}roboto300.woff2", method = "GET", method = "HEAD")]
 async fn roboto300() -> impl Responder { HttpResponse::Ok().content_type("font/woff2").body(ROBOTO300) }
 
-#[get("{_anypath:.*/|}favicon.ico")]
+#[route("{_anypath:.*/|}favicon.ico", method = "GET", method = "HEAD")]
 async fn favicon(r: HttpRequest) -> impl Responder { hash_etag(&r, &FAVICON[..], &FAVICON_SHASH, &FAVICON_SHASH_QUOTED, "image/x-icon") }
 
 fn error_response(source: &str, error: impl std::fmt::Debug) -> HttpResponse {
@@ -126,13 +242,6 @@ fn error_response(source: &str, error: impl std::fmt::Debug) -> HttpResponse {
     HttpResponse::InternalServerError().body(message)
 }
 
-fn res_result(resource: &str, content_type: &str, result: Result<String, Box<dyn Error>>) -> HttpResponse {
-    match result {
-        Ok(s) => HttpResponse::Ok().content_type(content_type).body(s),
-        Err(e) => error_response(&format!("resource {resource}"), e),
-    }
-}
-
 // Pseudo GET parameters with empty value so that asset responders still match and caching works.
 fn add_hashes(body: &str) -> String {
     body.replacen("rickview.css", &format!("rickview.css?{}", *RICKVIEW_CSS_SHASH), 1)
@@ -140,35 +249,113 @@ fn add_hashes(body: &str) -> String {
         .replacen("favicon.ico", &format!("favicon.ico?{}", *FAVICON_SHASH), 1)
 }
 
+/// Split a file name like `X.ttl` into the name and the format with that extension.
+fn split_extension(name: &str) -> Option<(&str, Format)> {
+    let (name, extension) = name.rsplit_once('.')?;
+    Some((name, Format::from_extension(extension)?))
+}
+
+/// The suffix of the resource and the format of a document URL like `X.ttl`, or `page/X` and `data/X.ttl` if `document_urls` is `path`.
+fn document(suffix: &str) -> Option<(&str, Format)> {
+    if config().document_urls == DocumentUrls::Path {
+        if let Some(resource) = suffix.strip_prefix("page/") {
+            return Some((resource, Format::Html));
+        }
+        if let Some(document) = suffix.strip_prefix("data/") {
+            return split_extension(document);
+        }
+    }
+    split_extension(suffix)
+}
+
+/// The path of the documents in RDF formats that describe the resource at the given path without the extension, see [`document`].
+fn data_path(path: &str) -> String {
+    let base = &config().base;
+    match config().document_urls {
+        DocumentUrls::Path => format!("{base}/data{}", path.strip_prefix(base.as_str()).unwrap_or(path)),
+        _ => path.to_owned(),
+    }
+}
+
+/// The root-relative URL of the document in the given format that describes the resource with the given suffix, see [`document`].
+fn document_url(suffix: &str, format: Format) -> String {
+    let base = &config().base;
+    let suffix = rdf::encode_path(suffix);
+    if config().document_urls == DocumentUrls::Path && format == Format::Html {
+        format!("{base}/page/{suffix}")
+    } else {
+        format!("{}.{}", data_path(&format!("{base}/{suffix}")), format.extension())
+    }
+}
+
 #[derive(Deserialize)]
 struct Params {
     output: Option<String>,
+    /// property IRI whose further values are requested as an HTML fragment, see [`rdf::property_page`]
+    prop: Option<String>,
+    /// page of further values of `prop`, only parsed together with it so that other links with these parameters still get the resource
+    page: Option<String>,
+    /// whether `prop` is an inverse property
+    inverse: Option<String>,
 }
 
-#[get("/{suffix:.*}")]
-/// Serve an RDF resource either as HTML or one of various serializations depending on the accept header.
+#[route("/{suffix:.*}", method = "GET", method = "HEAD")]
+/// Serve an RDF resource either as HTML or one of various serializations depending on the accept header or the "output" parameter.
 async fn rdf_resource(r: HttpRequest, suffix: web::Path<String>, params: web::Query<Params>) -> impl Responder {
-    const NT: &str = "application/n-triples";
-    const TTL: &str = "application/turtle";
-    #[cfg(feature = "rdfxml")]
-    const XML: &str = "application/rdf+xml";
-    const HTML: &str = "text/html";
-    let suffix: &str = &suffix;
-    let id = RUN_ID.load(Ordering::Relaxed).to_string();
+    let mut suffix: &str = &suffix;
+    // document URL like X.ttl that describes the resource X, unless there is a resource with that URI
+    let mut document_format = None;
+    // whether there are triples about the resource with the suffix, only looked up when needed and then at most once
+    let mut exists = None;
+    if let Some((resource, format)) = document(suffix) {
+        if rdf::exists(config().namespace.resolve(IriRef::new_unchecked(suffix)).as_ref()) {
+            exists = Some(true);
+        } else {
+            suffix = resource;
+            document_format = Some(format);
+        }
+    }
+    let prefixed = config().prefix.to_string() + ":" + suffix;
+    let accept = r.headers().get(header::ACCEPT).and_then(|a| a.to_str().ok());
+    trace!("{prefixed} accept header {accept:?}");
+    let iri = config().namespace.resolve(IriRef::new_unchecked(suffix));
+    if let Some(prop) = &params.prop {
+        let page = params.page.as_deref().and_then(|p| p.parse().ok()).unwrap_or(1);
+        let inverse = params.inverse.as_deref().is_some_and(|i| i.parse().unwrap_or(false));
+        return match rdf::property_page(iri.as_ref(), prop, inverse, page) {
+            Some(html) => HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html),
+            None => HttpResponse::BadRequest().content_type("text/plain").body(format!("Invalid property IRI {prop}")),
+        };
+    }
+    let output = params.output.as_deref();
+    let Some(format) = output.map_or_else(|| document_format.or_else(|| negotiate(accept)), Format::from_media_type) else {
+        let requested = output.or(accept).unwrap_or_default();
+        warn!("{prefixed} requested as {requested}, which is not supported");
+        return HttpResponse::NotAcceptable()
+            .content_type("text/plain")
+            .append_header(VARY)
+            .body(format!("Cannot serve {prefixed} as {requested}. Supported media types are {}.", Format::supported()));
+    };
+    if config().document_urls != DocumentUrls::None
+        && document_format.is_none()
+        && !suffix.is_empty()
+        && exists.unwrap_or_else(|| rdf::exists(iri.as_ref()))
+    {
+        return HttpResponse::SeeOther().append_header((header::LOCATION, document_url(suffix, format))).append_header(VARY).finish();
+    }
+    // representations in different formats need different entity tags
+    let id = format!("{}-{}", RUN_ID.load(Ordering::Relaxed), format.extension());
     let quoted = format!("\"{id}\"");
     if let Some(e) = r.headers().get(header::IF_NONE_MATCH)
         && let Ok(s) = e.to_str()
         && s == quoted
     {
-        return HttpResponse::NotModified().finish();
+        return HttpResponse::NotModified().append_header(VARY).finish();
     }
     let etag = ETag(EntityTag::new_strong(id));
-    let output = params.output.as_deref();
     let t = Instant::now();
-    let prefixed = config().prefix.to_string() + ":" + suffix;
 
-    let iri = config().namespace.resolve(IriRef::new_unchecked(suffix));
-    let mut res = rdf::resource(iri.as_ref());
+    let mut res = rdf::resource(iri.as_ref(), false);
     // no triples found
     if res.directs.is_empty() && res.inverses.is_empty() {
         // resource URI equal to namespace takes precedence
@@ -177,85 +364,293 @@ async fn rdf_resource(r: HttpRequest, suffix: web::Path<String>, params: web::Qu
         }
         let warning = format!("No triples found for {suffix}. Did you configure the namespace correctly?");
         warn!("{warning}");
-        if let Some(a) = r.head().headers().get("Accept")
-            && let Ok(accept) = a.to_str()
-            && accept.contains(HTML)
-        {
-            res.descriptions.push(("Warning".to_owned(), vec![warning.clone()]));
+        metrics::not_found();
+        if format == Format::Html {
+            res.descriptions.push(("Warning".to_owned(), vec![warning.clone()], None));
             // HTML is accepted and there are no errors, create a pseudo element in the empty resource to return 404 with HTML
-            return match template().render("resource", &Context { config: config(), resource: Some(res), about: None, page: None }) {
-                Ok(html) => HttpResponse::NotFound().content_type("text/html; charset-utf-8").append_header(etag).body(add_hashes(&html)),
-                Err(e) => HttpResponse::NotFound().content_type("text/plain").append_header(etag).body(format!("{warning}\n\n{e}")),
+            return match template().render("resource", &Context { config: TemplateConfig::new(false), resource: Some(res), about: None, page: None }) {
+                Ok(html) => {
+                    HttpResponse::NotFound().content_type("text/html; charset=utf-8").append_header(etag).append_header(VARY).body(add_hashes(&html))
+                }
+                Err(e) => HttpResponse::NotFound().content_type("text/plain").append_header(etag).append_header(VARY).body(format!("{warning}\n\n{e}")),
             };
         }
         // return 404 with plain text
-        return HttpResponse::NotFound().content_type("text/plain").append_header(etag).body(warning);
-    }
-    if let Some(a) = r.head().headers().get("Accept") {
-        if let Ok(accept) = a.to_str() {
-            trace!("{prefixed} accept header {accept}");
-            if accept.contains(NT) || output == Some(NT) {
-                debug!("{} N-Triples {:?}", prefixed, t.elapsed());
-                return res_result(&prefixed, NT, rdf::serialize_nt(iri.as_ref()));
-            }
-            #[cfg(feature = "rdfxml")]
-            if accept.contains(XML) || output == Some(XML) {
-                debug!("{} RDF/XML {:?}", prefixed, t.elapsed());
-                return res_result(&prefixed, XML, rdf::serialize_rdfxml(iri.as_ref()));
-            }
-            if accept.contains(HTML) && output != Some(TTL) {
-                let context = Context { config: config(), about: None, page: None, resource: Some(res) };
-                return match template().render("resource", &context) {
-                    Ok(html) => {
-                        debug!("{} HTML {:?}", prefixed, t.elapsed());
-                        HttpResponse::Ok().content_type("text/html; charset-utf-8").append_header(etag).body(add_hashes(&html))
-                    }
-                    Err(err) => error_response(&format!("resource {prefixed}"), err),
-                };
-            }
-            if !accept.contains(TTL) {
-                warn!("{prefixed} accept header {accept} and 'output' param {output:?} not recognized, default to RDF Turtle");
-            }
+        return HttpResponse::NotFound().content_type("text/plain").append_header(etag).append_header(VARY).body(warning);
+    }
+    let result = match format {
+        Format::Html => {
+            let context = Context { config: TemplateConfig::new(false), about: None, page: None, resource: Some(res) };
+            template().render("resource", &context).map(|html| add_hashes(&html)).map_err(Box::from)
         }
-    } else {
-        warn!("{prefixed} accept header missing, using RDF Turtle");
+        Format::Turtle => rdf::serialize_turtle(iri.as_ref()),
+        Format::NTriples => rdf::serialize_nt(iri.as_ref()),
+        #[cfg(feature = "rdfxml")]
+        Format::RdfXml => rdf::serialize_rdfxml(iri.as_ref()),
+        #[cfg(feature = "jsonld")]
+        Format::JsonLd => rdf::serialize_jsonld(iri.as_ref()),
+    };
+    match result {
+        Ok(body) => {
+            debug!("{prefixed} {format:?} {:?}", t.elapsed());
+            let content_type = if format == Format::Html { "text/html; charset=utf-8" } else { format.media_type() };
+            HttpResponse::Ok().content_type(content_type).append_header(etag).append_header(VARY).body(body)
+        }
+        Err(e) => error_response(&format!("resource {prefixed}"), e),
     }
-    debug!("{} RDF Turtle {:?}", prefixed, t.elapsed());
-    res_result(&prefixed, TTL, rdf::serialize_turtle(iri.as_ref()))
 }
 
 /// does not get shown when there is a resource whose URI equals the namespace, with or without slash
 fn index() -> HttpResponse {
-    let context = Context { config: config(), about: None, page: None, resource: None };
+    let context = Context { config: TemplateConfig::new(false), about: None, page: None, resource: None };
     match template().render("index", &context) {
         Ok(body) => HttpResponse::Ok().content_type("text/html").body(add_hashes(&body)),
         Err(e) => error_response("index page", e),
     }
 }
 
-#[get("/about")]
+#[route("/about", method = "GET", method = "HEAD")]
 async fn about_page() -> impl Responder {
-    let context = Context { config: config(), about: Some(About::new()), page: None, resource: None };
+    let context = Context { config: TemplateConfig::new(false), about: Some(About::new()), page: None, resource: None };
     match template().render("about", &context) {
         Ok(body) => HttpResponse::Ok().content_type("text/html").body(add_hashes(&body)),
         Err(e) => error_response("about page", e),
     }
 }
 
-#[get("/classes")]
+#[route("/classes", method = "GET", method = "HEAD")]
 async fn class_page() -> impl Responder {
     let body = crate::classes::class_tree();
-    let context = Context { config: config(), about: None, page: Some(Page { title: "Classes".to_owned(), body }), resource: None };
+    let context = Context { config: TemplateConfig::new(false), about: None, page: Some(Page { title: "Classes".to_owned(), body }), resource: None };
     match template().render("custom", &context) {
         Ok(body) => HttpResponse::Ok().content_type("text/html").body(add_hashes(&body)),
         Err(e) => error_response("class page", e),
     }
 }
 
-#[head("{_anypath:.*}")]
-async fn head() -> HttpResponse { HttpResponse::MethodNotAllowed().body("RickView does not support HEAD requests.") }
+#[route("/fragments", method = "GET", method = "HEAD")]
+/// Triple Pattern Fragments interface, see [`fragments::fragment`].
+async fn fragments_endpoint(r: HttpRequest, selector: web::Query<fragments::Selector>) -> impl Responder {
+    const OFFERED: &[&str] = &[fragments::TURTLE, fragments::TRIG];
+    let accept = r.headers().get(header::ACCEPT).and_then(|a| a.to_str().ok());
+    let Some(media_type) = negotiate_media_type(accept, OFFERED) else {
+        return HttpResponse::NotAcceptable()
+            .content_type("text/plain")
+            .append_header(VARY)
+            .body(format!("Supported media types for fragments are {}.", OFFERED.join(", ")));
+    };
+    let dataset_url = {
+        let info = r.connection_info();
+        format!("{}://{}{}/fragments", info.scheme(), info.host(), config().base)
+    };
+    let t = Instant::now();
+    let result = web::block(move || fragments::fragment(&selector, &dataset_url, media_type)).await;
+    match result {
+        Ok(Ok(body)) => {
+            debug!("fragment {} {:?}", r.uri(), t.elapsed());
+            HttpResponse::Ok().content_type(media_type).append_header(VARY).body(body)
+        }
+        Ok(Err(fragments::FragmentError::BadRequest(message))) => HttpResponse::BadRequest().content_type("text/plain").body(message),
+        Ok(Err(fragments::FragmentError::Internal(message))) => error_response("fragment", message),
+        Err(e) => error_response("fragment", e),
+    }
+}
 
-#[get("")]
+#[route("/metrics", method = "GET", method = "HEAD")]
+/// Request and knowledge base metrics for Prometheus.
+async fn metrics_page() -> impl Responder {
+    match web::block(metrics::render).await {
+        Ok(body) => HttpResponse::Ok().content_type("text/plain; version=0.0.4; charset=utf-8").body(body),
+        Err(e) => error_response("metrics", e),
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: Option<String>,
+    /// maximum number of results
+    limit: Option<usize>,
+}
+
+#[route("/search", method = "GET", method = "HEAD")]
+/// Search resources by title and description, as an HTML page or as JSON for autocompletion.
+async fn search_page(r: HttpRequest, params: web::Query<SearchParams>) -> impl Responder {
+    const OFFERED: &[&str] = &["text/html", "application/json"];
+    let accept = r.headers().get(header::ACCEPT).and_then(|a| a.to_str().ok());
+    let Some(media_type) = negotiate_media_type(accept, OFFERED) else {
+        return HttpResponse::NotAcceptable()
+            .content_type("text/plain")
+            .append_header(VARY)
+            .body(format!("Supported media types are {}.", OFFERED.join(", ")));
+    };
+    let SearchParams { q, limit } = params.into_inner();
+    let query = q.unwrap_or_default();
+    let json = media_type == "application/json";
+    let limit = limit.unwrap_or(if json { 10 } else { 100 });
+    let t = Instant::now();
+    let q = query.clone();
+    let hits = match web::block(move || rdf::kb().search_index().map(|index| index.search(&q, limit))).await {
+        Ok(hits) => hits,
+        Err(e) => return error_response("search results", e),
+    };
+    debug!("search for {query} {:?}", t.elapsed());
+    if json {
+        return HttpResponse::Ok().append_header(VARY).json(hits.map(|(_, hits)| hits).unwrap_or_default());
+    }
+    let body = match hits {
+        Some((total, hits)) => search::html(&query, total, &hits),
+        None => "<p>Search is not available because the titles of this knowledge base are not indexed.</p>".to_owned(),
+    };
+    let context = Context { config: TemplateConfig::new(false), about: None, page: Some(Page { title: "Search".to_owned(), body }), resource: None };
+    match template().render("custom", &context) {
+        Ok(body) => HttpResponse::Ok().content_type("text/html; charset=utf-8").append_header(VARY).body(add_hashes(&body)),
+        Err(e) => error_response("search page", e),
+    }
+}
+
+#[cfg(feature = "sparql")]
+#[derive(Deserialize)]
+struct SparqlParams {
+    query: Option<String>,
+}
+
+#[cfg(feature = "sparql")]
+#[route("/sparql", method = "GET", method = "HEAD", method = "POST")]
+/// SPARQL endpoint over the knowledge base with a query form for browsers, see [`sparql::query`].
+/// POST requests contain either the query directly or URL-encoded parameters.
+async fn sparql_endpoint(r: HttpRequest, params: web::Query<SparqlParams>, body: String) -> impl Responder {
+    let direct = r.headers().get(header::CONTENT_TYPE).and_then(|c| c.to_str().ok()).is_some_and(|c| c.starts_with("application/sparql-query"));
+    let query = if direct {
+        Some(body)
+    } else if body.is_empty() {
+        params.into_inner().query
+    } else {
+        match web::Query::<SparqlParams>::from_query(&body) {
+            Ok(p) => p.into_inner().query,
+            Err(e) => return HttpResponse::BadRequest().content_type("text/plain").body(e.to_string()),
+        }
+    };
+    let page = |query: &str, result: &str| {
+        let body = sparql::form(query) + result;
+        let context = Context { config: TemplateConfig::new(false), about: None, page: Some(Page { title: "SPARQL".to_owned(), body }), resource: None };
+        match template().render("custom", &context) {
+            Ok(body) => HttpResponse::Ok().content_type("text/html; charset=utf-8").append_header(VARY).body(add_hashes(&body)),
+            Err(e) => error_response("SPARQL page", e),
+        }
+    };
+    let Some(query) = query.filter(|q| !q.trim().is_empty()) else {
+        return page("", "");
+    };
+    let accept = r.headers().get(header::ACCEPT).and_then(|a| a.to_str().ok()).map(str::to_owned);
+    let t = Instant::now();
+    let q = query.clone();
+    let answer = match web::block(move || sparql::query(&q, accept.as_deref())).await {
+        Ok(answer) => answer,
+        Err(e) => return error_response("SPARQL result", e),
+    };
+    debug!("SPARQL query answered in {:?}", t.elapsed());
+    match answer {
+        Ok(answer) if answer.media_type == sparql::HTML => page(&query, &answer.body),
+        Ok(answer) => HttpResponse::Ok().content_type(answer.media_type).append_header(VARY).body(answer.body),
+        Err(sparql::QueryError::BadRequest(message)) => HttpResponse::BadRequest().content_type("text/plain").body(message),
+        Err(sparql::QueryError::NotAcceptable(offered)) => HttpResponse::NotAcceptable()
+            .content_type("text/plain")
+            .append_header(VARY)
+            .body(format!("Supported media types for this query are {}.", offered.join(", "))),
+        Err(sparql::QueryError::Timeout(timeout)) => {
+            warn!("SPARQL query timed out after {timeout:?}: {query}");
+            HttpResponse::ServiceUnavailable().content_type("text/plain").body(format!("Query timed out after {timeout:?}."))
+        }
+        Err(sparql::QueryError::Busy) => HttpResponse::ServiceUnavailable()
+            .content_type("text/plain")
+            .append_header((header::RETRY_AFTER, "1"))
+            .body("Too many queries are running, try again later."),
+        Err(sparql::QueryError::Internal(message)) => error_response("SPARQL result", message),
+    }
+}
+
+/// Reload the templates and the knowledge base unless another reload is still running, see [`reload_with`].
+fn reload_kb() {
+    match rdf::ReloadGuard::acquire() {
+        Some(guard) => reload_with(&guard),
+        None => warn!("Skipping reload because another reload is still running"),
+    }
+}
+
+/// Reload the templates and the knowledge base and invalidate the client caches via the entity tag if successful.
+/// Blocks until the reload is finished.
+fn reload_with(guard: &rdf::ReloadGuard) {
+    match load_templates() {
+        Ok(templates) => set_templates(templates),
+        Err(e) => error!("Keeping the current templates: {e:?}"),
+    }
+    match rdf::reload(guard) {
+        Ok(()) => {
+            RUN_ID.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(e) => error!("{e:?}"),
+    }
+}
+
+#[post("/admin/reload")]
+/// Reload the knowledge base in the background, authorized by the configured admin token.
+async fn admin_reload(r: HttpRequest) -> impl Responder {
+    let Some(token) = &config().admin_token else {
+        return HttpResponse::NotFound().finish();
+    };
+    let authorized = r
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|a| a.to_str().ok())
+        .and_then(|a| a.strip_prefix("Bearer "))
+        .is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes()));
+    if !authorized {
+        return HttpResponse::Unauthorized().append_header((header::WWW_AUTHENTICATE, "Bearer")).body("Missing or invalid admin token.");
+    }
+    let Some(guard) = rdf::ReloadGuard::acquire() else {
+        return HttpResponse::Conflict().body("Another reload is still running, try again later.");
+    };
+    std::thread::spawn(move || reload_with(&guard));
+    HttpResponse::Accepted().body("Reloading the knowledge base in the background.")
+}
+
+/// Compare all bytes of equally long inputs so that the time taken does not reveal how many leading bytes of a secret are correct.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool { a.len() == b.len() && std::hint::black_box(a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y))) == 0 }
+
+#[route("/healthz", method = "GET", method = "HEAD")]
+/// Liveness probe, which only fails if the knowledge base could not be loaded so that the instance can be restarted.
+async fn healthz() -> impl Responder {
+    match rdf::status() {
+        status @ rdf::Status::Failed(_) => HttpResponse::ServiceUnavailable().content_type("text/plain").body(status.to_string()),
+        _ => HttpResponse::Ok().content_type("text/plain").body("OK"),
+    }
+}
+
+#[route("/readyz", method = "GET", method = "HEAD")]
+/// Readiness probe, which succeeds once the knowledge base is loaded and otherwise reports the loading progress or error.
+/// Starts loading the knowledge base in the background if that has not happened yet because `preload` is disabled.
+async fn readyz() -> impl Responder {
+    rdf::load_in_background();
+    let status = rdf::status();
+    let code = if matches!(status, rdf::Status::Loaded) { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    HttpResponse::build(code).content_type("text/plain").body(status.to_string())
+}
+
+/// Wait until the knowledge base is loaded before handling a request that needs it and respond with 503 if loading failed.
+async fn require_kb(req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    // a reload can recover from a failed load
+    let independent = matches!(req.match_name(), Some("metrics_page" | "admin_reload" | "redirect"));
+    if !independent
+        && rdf::loaded().is_none()
+        && let Err(e) = web::block(rdf::load).await?
+    {
+        let response = HttpResponse::ServiceUnavailable().content_type("text/plain").body(format!("Failed to load knowledge base: {e:#}"));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+#[route("", method = "GET", method = "HEAD")]
 /// redirect /base to correct index page /base/
 /// For example, a user may erroneously open <http://mydomain.org/ontology> but mean <http://mydomain.org/ontology/>, which should be the base resource if it exists as the latter is inside the namespace.
 async fn redirect() -> impl Responder { HttpResponse::TemporaryRedirect().append_header(("location", config().base.clone() + "/")).finish() }
@@ -266,18 +661,222 @@ async fn main() -> std::io::Result<()> {
     #[allow(clippy::cast_possible_truncation)]
     RUN_ID.store(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u32, Ordering::Relaxed);
     config(); // enable logging
-    info!("RickView {} serving {} at http://localhost:{}{}/", config::VERSION, config().namespace.as_str(), config().port, config().base);
-    HttpServer::new(move || {
+    if config::args().print_config {
+        println!("{}", serde_json::to_string_pretty(config()).map_err(std::io::Error::other)?);
+        return Ok(());
+    }
+    let templates = load_templates().map_err(|e| io::Error::other(format!("{e:?}")))?;
+    set_templates(templates);
+    if let Some(config::Command::Export { dir }) = &config::args().command {
+        return export::export(dir).map_err(|e| std::io::Error::other(format!("{e:?}")));
+    }
+    info!("RickView {} serving {}", config::VERSION, config().namespace.as_str());
+    if config().preload {
+        rdf::load_in_background();
+    }
+    if let Some(secs) = config().watch_interval {
+        watch::spawn(Duration::from_secs(secs), reload_kb);
+    }
+    let mut server = HttpServer::new(move || {
         App::new()
             .wrap(Compress::default())
+            .wrap_fn(|req, srv| {
+                let t = Instant::now();
+                let response = srv.call(req);
+                async move {
+                    let response = response.await?;
+                    metrics::record(&response, t.elapsed());
+                    Ok(response)
+                }
+            })
             .service(rickview_css)
             .service(roboto_css)
             .service(roboto300)
             .service(favicon)
-            .service(head)
-            .service(scope(&config().base).service(about_page).service(class_page).service(rdf_resource).service(redirect))
-    })
-    .bind(("0.0.0.0", config().port))?
-    .run()
-    .await
+            .service(healthz)
+            .service(readyz)
+            .service({
+                let scope = scope(&config().base)
+                    .service(about_page)
+                    .service(class_page)
+                    .service(admin_reload)
+                    .service(fragments_endpoint)
+                    .service(search_page)
+                    .service(metrics_page);
+                #[cfg(feature = "sparql")]
+                let scope = scope.service(sparql_endpoint);
+                scope.service(rdf_resource).service(redirect).wrap(from_fn(require_kb))
+            })
+    });
+    let tls = tls_config()?;
+    for address in &config().bind {
+        if let Some(path) = address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                remove_stale_socket(path)?;
+                info!("Listening on Unix domain socket {path}");
+                server = server.bind_uds(path)?;
+                continue;
+            }
+            #[cfg(not(unix))]
+            return Err(io::Error::other(format!("Unix domain socket {path} is not supported on this platform")));
+        }
+        let addresses: Vec<SocketAddr> = match address.parse::<SocketAddr>() {
+            Ok(a) => vec![a],
+            Err(_) => (address.as_str(), config().port).to_socket_addrs()?.collect(),
+        };
+        for a in &addresses {
+            info!("Listening on {}://{a}{}/", if tls.is_some() { "https" } else { "http" }, config().base);
+        }
+        server = match &tls {
+            #[cfg(feature = "tls")]
+            Some(tls) => server.bind_rustls_0_23(&addresses[..], tls.clone())?,
+            _ => server.bind(&addresses[..])?,
+        };
+    }
+    server.run().await
+}
+
+#[cfg(feature = "tls")]
+type TlsConfig = rustls::ServerConfig;
+#[cfg(not(feature = "tls"))]
+type TlsConfig = ();
+
+/// TLS configuration from the configured certificate and key files, None if they are not configured.
+fn tls_config() -> io::Result<Option<TlsConfig>> {
+    let c = config();
+    let (Some(cert), Some(key)) = (&c.tls_cert, &c.tls_key) else {
+        if c.tls_cert.is_some() || c.tls_key.is_some() {
+            warn!("TLS needs both tls_cert and tls_key, serving HTTP");
+        }
+        return Ok(None);
+    };
+    #[cfg(feature = "tls")]
+    {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+        let certs: Vec<_> = CertificateDer::pem_file_iter(cert)
+            .and_then(Iterator::collect)
+            .map_err(|e| io::Error::other(format!("Cannot read TLS certificate {cert}: {e}")))?;
+        let key = PrivateKeyDer::from_pem_file(key).map_err(|e| io::Error::other(format!("Cannot read TLS key {key}: {e}")))?;
+        let tls = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| io::Error::other(format!("Invalid TLS certificate {cert} or key: {e}")))?;
+        Ok(Some(tls))
+    }
+    #[cfg(not(feature = "tls"))]
+    Err(io::Error::other(format!("Cannot use TLS certificate {cert} and key {key} because RickView was compiled without the tls feature")))
+}
+
+/// Remove a Unix domain socket left over from a previous run, which would prevent binding to it.
+/// Fails if another process still listens on it or if the path is not a socket, which are left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &str) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Cannot listen on {path}, which exists and is not a socket")));
+    }
+    match UnixStream::connect(path) {
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            info!("Removing stale socket {path}");
+            fs::remove_file(path)
+        }
+        Ok(_) => Err(io::Error::new(io::ErrorKind::AddrInUse, format!("Another process is listening on {path}"))),
+        Err(e) => Err(io::Error::new(e.kind(), format!("Cannot check whether socket {path} is stale: {e}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_to_suffix() {
+        let mut tt = compile_templates(&[]);
+        tt.add_template("suffix", "{ uri | uri_to_suffix }").unwrap();
+        let suffix = |uri: serde_json::Value| tt.render("suffix", &serde_json::json!({ "uri": uri }));
+        assert_eq!(suffix("http://example.com/resource/A".into()).unwrap(), "A");
+        assert_eq!(suffix("http://example.com/ontology#B".into()).unwrap(), "B");
+        assert_eq!(suffix("urn:isbn:123".into()).unwrap(), "urn:isbn:123");
+        assert_eq!(suffix("javascript:alert('<b>')".into()).unwrap(), "javascript:alert(&#39;&lt;b&gt;&#39;)");
+        assert_eq!(suffix("http://example.com/x'onclick='alert(1)".into()).unwrap(), "x&#39;onclick=&#39;alert(1)");
+        assert!(suffix(1.into()).is_err());
+    }
+
+    #[test]
+    fn replaced_templates_are_freed() {
+        let old = TEMPLATES.read().unwrap().clone();
+        assert!(template().render("index", &Context { config: TemplateConfig::new(false), about: None, resource: None, page: None }).is_ok());
+        let mut templates: Vec<_> = old.to_vec();
+        templates[0].1.push_str("<!-- replaced -->");
+        set_templates(templates);
+        let html = template().render("index", &Context { config: TemplateConfig::new(false), about: None, resource: None, page: None }).unwrap();
+        assert!(html.contains("<!-- replaced -->"));
+        assert_eq!(Arc::strong_count(&old), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_sockets() {
+        use std::os::unix::net::UnixListener;
+        let dir = std::env::temp_dir().join(format!("rickview-sockets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+        assert!(remove_stale_socket(&path("missing.sock")).is_ok());
+        fs::write(path("file.sock"), "data").unwrap();
+        assert!(remove_stale_socket(&path("file.sock")).is_err());
+        assert_eq!(fs::read_to_string(path("file.sock")).unwrap(), "data");
+        let listener = UnixListener::bind(path("live.sock")).unwrap();
+        assert_eq!(remove_stale_socket(&path("live.sock")).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+        assert!(Path::new(&path("live.sock")).exists());
+        // the socket file outlives the listener
+        drop(listener);
+        assert!(remove_stale_socket(&path("live.sock")).is_ok());
+        assert!(!Path::new(&path("live.sock")).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "sparql")]
+    #[actix_web::test]
+    async fn sparql_endpoint_results() {
+        use actix_web::test;
+        rdf::tests::test_kb();
+        let app = test::init_service(App::new().service(sparql_endpoint)).await;
+        let request = |query: &str, accept: &str| {
+            test::TestRequest::get().uri(&format!("/sparql?query={}", rdf::encode_query_value(query))).insert_header((header::ACCEPT, accept)).to_request()
+        };
+        let json = |query: &str| test::call_and_read_body_json::<_, _, serde_json::Value>(&app, request(query, "application/sparql-results+json"));
+
+        // SELECT * has the variables in the order of the pattern, explicit ones in the given order
+        let star = json("PREFIX ex: <http://example.com/resource/select*>\nSELECT * WHERE { ?s ?p ?o } LIMIT 2").await;
+        assert_eq!(star["head"]["vars"], serde_json::json!(["s", "p", "o"]));
+        assert_eq!(star["results"]["bindings"].as_array().unwrap().len(), 2);
+        let explicit = json("SELECT ?o ?s WHERE { ?s <http://www.w3.org/2000/01/rdf-schema#label> ?o }").await;
+        assert_eq!(explicit["head"]["vars"], serde_json::json!(["o", "s"]));
+        let good = serde_json::json!({"o": {"type": "literal", "value": "good"}, "s": {"type": "uri", "value": "http://example.com/resource/Good"}});
+        assert!(explicit["results"]["bindings"].as_array().unwrap().contains(&good));
+        let csv = test::call_and_read_body(&app, request("SELECT * WHERE { ?s ?p \"good\" }", "text/csv")).await;
+        assert_eq!(csv, "s,p\r\nhttp://example.com/resource/Good,http://www.w3.org/2000/01/rdf-schema#label\r\n");
+
+        assert_eq!(json("ASK { <http://example.com/resource/Good> ?p \"good\" }").await, serde_json::json!({"head": {}, "boolean": true}));
+        assert_eq!(json("ASK { <http://example.com/resource/Good> ?p \"bad\" }").await, serde_json::json!({"head": {}, "boolean": false}));
+
+        let construct = "CONSTRUCT { ?s <http://example.com/resource/named> ?o } WHERE { ?s <http://www.w3.org/2000/01/rdf-schema#label> \"good\" ; ?p ?o FILTER(isLiteral(?o)) }";
+        let response = test::call_service(&app, request(construct, "application/n-triples")).await;
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/n-triples");
+        let nt = test::read_body(response).await;
+        assert_eq!(nt, "<http://example.com/resource/Good> <http://example.com/resource/named> \"good\".\n");
+
+        for bad in ["SELECT WHERE", "SELECT * FROM <http://example.com/g> WHERE { ?s ?p ?o }"] {
+            assert_eq!(test::call_service(&app, request(bad, "application/sparql-results+json")).await.status(), StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(test::call_service(&app, request("ASK {}", "text/csv")).await.status(), StatusCode::NOT_ACCEPTABLE);
+    }
 }
@@ -0,0 +1,96 @@
+//! Prometheus metrics about requests and the knowledge base, see <https://prometheus.io/docs/instrumenting/exposition_formats/>.
+use crate::rdf;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// upper bounds of the request duration histogram buckets in seconds
+const BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0];
+
+/// Number of requests in each bucket, which are cumulative like in the exposition format.
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Requests {
+    /// (route, format, status) -> number of requests
+    counts: BTreeMap<(String, String, u16), u64>,
+    /// (route, format) -> durations
+    durations: BTreeMap<(String, String), Histogram>,
+}
+
+static REQUESTS: Mutex<Requests> = Mutex::new(Requests { counts: BTreeMap::new(), durations: BTreeMap::new() });
+static NOT_FOUND: AtomicU64 = AtomicU64::new(0);
+
+/// Count a response by the pattern of its route and its media type and add its duration to the histogram.
+pub fn record<B>(response: &ServiceResponse<B>, duration: Duration) {
+    let route = response.request().match_pattern().unwrap_or_else(|| "unmatched".to_owned());
+    let format = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|c| c.to_str().ok())
+        .map_or("none", |c| c.split(';').next().unwrap_or_default().trim())
+        .to_owned();
+    let seconds = duration.as_secs_f64();
+    let mut requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+    *requests.counts.entry((route.clone(), format.clone(), response.status().as_u16())).or_default() += 1;
+    let histogram = requests.durations.entry((route, format)).or_default();
+    for (bucket, le) in histogram.buckets.iter_mut().zip(BUCKETS) {
+        if seconds <= le {
+            *bucket += 1;
+        }
+    }
+    histogram.sum += seconds;
+    histogram.count += 1;
+}
+
+/// Count a requested resource for which no triples were found.
+pub fn not_found() { NOT_FOUND.fetch_add(1, Ordering::Relaxed); }
+
+/// Escape a label value.
+fn label(s: &str) -> String { s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n") }
+
+fn metric(s: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(s, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}");
+}
+
+/// All metrics in the Prometheus text format.
+pub fn render() -> String {
+    let mut s = String::new();
+    {
+        let requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+        s += "# HELP rickview_requests_total HTTP requests by route pattern, response media type and status.\n# TYPE rickview_requests_total counter\n";
+        for ((route, format, status), count) in &requests.counts {
+            let _ = writeln!(s, "rickview_requests_total{{route=\"{}\",format=\"{}\",status=\"{status}\"}} {count}", label(route), label(format));
+        }
+        s += "# HELP rickview_request_duration_seconds HTTP request duration by route pattern and response media type.\n# TYPE rickview_request_duration_seconds histogram\n";
+        for ((route, format), histogram) in &requests.durations {
+            let labels = format!("route=\"{}\",format=\"{}\"", label(route), label(format));
+            for (count, le) in histogram.buckets.iter().zip(BUCKETS) {
+                let _ = writeln!(s, "rickview_request_duration_seconds_bucket{{{labels},le=\"{le}\"}} {count}");
+            }
+            let _ = writeln!(s, "rickview_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {}", histogram.count);
+            let _ = writeln!(s, "rickview_request_duration_seconds_sum{{{labels}}} {}", histogram.sum);
+            let _ = writeln!(s, "rickview_request_duration_seconds_count{{{labels}}} {}", histogram.count);
+        }
+    }
+    metric(&mut s, "rickview_resources_not_found_total", "counter", "Requested resources without triples.", NOT_FOUND.load(Ordering::Relaxed));
+    metric(&mut s, "rickview_kb_loaded", "gauge", "Whether the knowledge base is loaded.", u8::from(rdf::loaded().is_some()));
+    let Some(kb) = rdf::loaded() else {
+        return s;
+    };
+    let triples: usize = kb.sources.iter().map(|(_, n)| n).sum();
+    metric(&mut s, "rickview_graph_triples", "gauge", "Triples loaded from the knowledge base sources.", triples);
+    metric(&mut s, "rickview_titles", "gauge", "Resources in the title index.", kb.titles().len());
+    metric(&mut s, "rickview_types", "gauge", "Resources in the type index.", kb.types().len());
+    metric(&mut s, "rickview_graph_load_seconds", "gauge", "Time it took to load the graph.", kb.load_time.as_secs_f64());
+    s
+}
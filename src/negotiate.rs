@@ -0,0 +1,210 @@
+//! HTTP content negotiation of the output format of resources, fragments and SPARQL query results, see <https://www.rfc-editor.org/rfc/rfc9110#name-accept>.
+
+/// Formats that a resource can be served in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Turtle,
+    NTriples,
+    #[cfg(feature = "rdfxml")]
+    RdfXml,
+    #[cfg(feature = "jsonld")]
+    JsonLd,
+    Html,
+}
+
+impl Format {
+    /// All formats in the order of preference for equally acceptable formats, so that `*/*` gets Turtle.
+    pub const ALL: &[Format] = &[
+        Format::Turtle,
+        Format::Html,
+        Format::NTriples,
+        #[cfg(feature = "rdfxml")]
+        Format::RdfXml,
+        #[cfg(feature = "jsonld")]
+        Format::JsonLd,
+    ];
+
+    /// Media types that select this format, starting with the registered one that is used as Content-Type.
+    fn media_types(self) -> &'static [&'static str] {
+        match self {
+            Format::Turtle => &["text/turtle", "application/turtle", "application/x-turtle"],
+            Format::NTriples => &["application/n-triples"],
+            #[cfg(feature = "rdfxml")]
+            Format::RdfXml => &["application/rdf+xml"],
+            #[cfg(feature = "jsonld")]
+            Format::JsonLd => &["application/ld+json"],
+            Format::Html => &["text/html", "application/xhtml+xml"],
+        }
+    }
+
+    pub fn media_type(self) -> &'static str { self.media_types()[0] }
+
+    /// File extension of document URLs like `X.ttl`, also used to distinguish the entity tags of the different representations of a resource.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Turtle => "ttl",
+            Format::NTriples => "nt",
+            #[cfg(feature = "rdfxml")]
+            Format::RdfXml => "rdf",
+            #[cfg(feature = "jsonld")]
+            Format::JsonLd => "jsonld",
+            Format::Html => "html",
+        }
+    }
+
+    /// The format with exactly this media type, ignoring case and parameters, for the "output" query parameter.
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        let media_type = media_type.split(';').next().unwrap_or_default().trim();
+        Format::ALL.iter().copied().find(|f| f.media_types().iter().any(|m| m.eq_ignore_ascii_case(media_type)))
+    }
+
+    /// The format with this file extension, see [`Format::extension`].
+    pub fn from_extension(extension: &str) -> Option<Format> { Format::ALL.iter().copied().find(|f| f.extension() == extension) }
+
+    /// Registered media types of all formats, to tell clients what they can ask for.
+    pub fn supported() -> String { Format::ALL.iter().map(|f| f.media_type()).collect::<Vec<_>>().join(", ") }
+}
+
+/// A media range like `text/*;q=0.5` from an Accept header.
+struct MediaRange<'a> {
+    main: &'a str,
+    sub: &'a str,
+    q: f32,
+}
+
+impl MediaRange<'_> {
+    /// How well this range matches the media type: 2 for an exact match, 1 for `type/*`, 0 for `*/*` and None if it doesn't.
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        let (main, sub) = media_type.split_once('/')?;
+        if self.main == "*" {
+            Some(0)
+        } else if !self.main.eq_ignore_ascii_case(main) {
+            None
+        } else if self.sub == "*" {
+            Some(1)
+        } else if self.sub.eq_ignore_ascii_case(sub) {
+            Some(2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Media ranges of an Accept header, skipping invalid ones.
+fn parse(accept: &str) -> Vec<MediaRange<'_>> {
+    let mut ranges = Vec::new();
+    for range in accept.split(',') {
+        let mut parts = range.split(';').map(str::trim);
+        let Some((main, sub)) = parts.next().and_then(|mt| mt.split_once('/')) else {
+            continue;
+        };
+        let mut q = Some(1.0);
+        for param in parts {
+            if let Some((key, value)) = param.split_once('=')
+                && key.trim().eq_ignore_ascii_case("q")
+            {
+                q = value.trim().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q));
+            }
+        }
+        if let Some(q) = q {
+            ranges.push(MediaRange { main: main.trim(), sub: sub.trim(), q });
+        }
+    }
+    ranges
+}
+
+/// The specificity and weight of the most specific media range that matches the media type, None if none matches.
+fn quality(ranges: &[MediaRange<'_>], media_type: &str) -> Option<(u8, f32)> {
+    ranges.iter().filter_map(|r| Some((r.specificity(media_type)?, r.q))).max_by_key(|(specificity, _)| *specificity)
+}
+
+/// The most acceptable candidate for the given Accept header, where each candidate has one or more media types.
+/// The first candidate if there is no Accept header and None if no candidate is acceptable.
+/// Between equally acceptable candidates, the one listed first is chosen.
+fn best<T: Copy>(accept: Option<&str>, candidates: &[(T, &[&str])]) -> Option<T> {
+    let Some(accept) = accept.filter(|a| !a.trim().is_empty()) else {
+        return candidates.first().map(|(c, _)| *c);
+    };
+    let ranges = parse(accept);
+    let mut best: Option<(T, f32)> = None;
+    for &(candidate, media_types) in candidates {
+        // a media type that is named explicitly, such as text/turtle;q=0, takes precedence over wildcards matching the aliases
+        let q = media_types
+            .iter()
+            .filter_map(|m| quality(&ranges, m))
+            .max_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map_or(0.0, |(_, q)| q);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((candidate, q));
+        }
+    }
+    best.map(|(candidate, _)| candidate)
+}
+
+/// The most acceptable format for the given Accept header, Turtle if there is none and None if no format is acceptable.
+pub fn negotiate(accept: Option<&str>) -> Option<Format> {
+    let candidates: Vec<_> = Format::ALL.iter().map(|&f| (f, f.media_types())).collect();
+    best(accept, &candidates)
+}
+
+/// The most acceptable of the offered media types for the given Accept header, the first one if there is none.
+pub fn negotiate_media_type(accept: Option<&str>, offered: &[&'static str]) -> Option<&'static str> {
+    let candidates: Vec<_> = offered.iter().map(|m| (*m, std::slice::from_ref(m))).collect();
+    best(accept, &candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_accept_header() {
+        assert_eq!(negotiate(None), Some(Format::Turtle));
+        assert_eq!(negotiate(Some("  ")), Some(Format::Turtle));
+        assert_eq!(negotiate_media_type(None, &["text/html", "application/json"]), Some("text/html"));
+    }
+
+    #[test]
+    fn quality_values() {
+        assert_eq!(negotiate(Some("text/turtle;q=0.5, text/html")), Some(Format::Html));
+        assert_eq!(negotiate(Some("text/turtle;q=1, text/html;q=0.5")), Some(Format::Turtle));
+        assert_eq!(negotiate(Some("application/n-triples; Q=0.9, text/turtle;q=0.8")), Some(Format::NTriples));
+        // ties go to the format listed first
+        assert_eq!(negotiate(Some("text/html, text/turtle")), Some(Format::Turtle));
+    }
+
+    #[test]
+    fn zero_quality_excludes() {
+        assert_eq!(negotiate(Some("text/turtle;q=0")), None);
+        assert_eq!(negotiate(Some("*/*, text/turtle;q=0")), Some(Format::Html));
+        assert_eq!(negotiate(Some("text/*, text/html;q=0, text/turtle;q=0")), None);
+        assert_eq!(negotiate_media_type(Some("text/html;q=0"), &["text/html"]), None);
+    }
+
+    #[test]
+    fn wildcard_precedence() {
+        assert_eq!(negotiate(Some("*/*")), Some(Format::Turtle));
+        // the more specific text/* applies to HTML instead of */*
+        assert_eq!(negotiate_media_type(Some("*/*;q=0.9, text/*;q=0.1"), &["text/html", "application/json"]), Some("application/json"));
+        assert_eq!(negotiate_media_type(Some("text/*;q=0.1, */*;q=0.9"), &["text/html", "application/json"]), Some("application/json"));
+        assert_eq!(negotiate(Some("*/*;q=0.1, text/*;q=0.9")), Some(Format::Turtle));
+        assert_eq!(negotiate(Some("text/*;q=0.5, text/html")), Some(Format::Html));
+        assert_eq!(negotiate(Some("image/*")), None);
+    }
+
+    #[test]
+    fn malformed_ranges_are_skipped() {
+        assert_eq!(negotiate(Some("text/turtle;q=abc, text/html;q=0.1")), Some(Format::Html));
+        assert_eq!(negotiate(Some("text/turtle;q=2, text/html;q=0.1")), Some(Format::Html));
+        assert_eq!(negotiate(Some("text/turtle;q=-1")), None);
+        assert_eq!(negotiate(Some("turtle, , text/html")), Some(Format::Html));
+        assert_eq!(negotiate(Some("garbage")), None);
+    }
+
+    #[test]
+    fn media_type_parameters() {
+        assert_eq!(Format::from_media_type("Text/Turtle; charset=utf-8"), Some(Format::Turtle));
+        assert_eq!(Format::from_media_type("text/*"), None);
+        assert_eq!(negotiate(Some("text/turtle;charset=utf-8;q=0.3, application/n-triples;q=0.2")), Some(Format::Turtle));
+    }
+}
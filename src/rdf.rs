@@ -1,22 +1,35 @@
 //! Load the RDF graph and summarize RDF resources.
 #![allow(rustdoc::bare_urls)]
-use crate::config::config;
+use crate::config::{BoundedDescription, config};
+use crate::endpoint::EndpointGraph;
+use crate::index_cache::{self, Indexes};
 use crate::resource::Resource;
+use crate::search;
 use anyhow::{Context, Result};
 #[cfg(feature = "hdt")]
 use hdt::Hdt;
 use log::*;
+use lru::LruCache;
 use multimap::MultiMap;
+use sophia::api::dataset::{Dataset, MutableDataset};
 use sophia::api::graph::{Graph, MutableGraph};
 use sophia::api::prefix::{Prefix, PrefixMap};
-use sophia::api::prelude::Triple;
+use sophia::api::ns::rdf;
+use sophia::api::prelude::{Quad, Triple};
+#[cfg(feature = "jsonld")]
+use sophia::api::serializer::QuadSerializer;
 use sophia::api::serializer::{Stringifier, TripleSerializer};
+use sophia::api::source::{QuadSource, TripleSource};
 use sophia::api::term::bnode_id::BnodeId;
 use sophia::api::term::matcher::{Any, TermMatcher};
 use sophia::api::term::{FromTerm, SimpleTerm, Term};
+use sophia::inmem::dataset::FastDataset;
 use sophia::inmem::graph::FastGraph;
+use sophia::inmem::index::TermIndexFullError;
 use sophia::iri::{Iri, IriRef};
-use sophia::turtle::parser::{nt, turtle};
+#[cfg(feature = "jsonld")]
+use sophia::jsonld::{self, JsonLdOptions, serializer::JsonLdSerializer};
+use sophia::turtle::parser::{nq, nt, trig, turtle};
 use sophia::turtle::serializer::nt::NtSerializer;
 use sophia::turtle::serializer::turtle::{TurtleConfig, TurtleSerializer};
 #[cfg(feature = "rdfxml")]
@@ -26,18 +39,37 @@ use std::convert::Infallible;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader};
+use std::num::NonZeroUsize;
 use std::path::Path;
-use std::sync::OnceLock;
-use std::time::Instant;
-#[cfg(feature = "hdt")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 use zstd::stream::read::Decoder;
 
 static EXAMPLE_KB: &str = std::include_str!("../data/example.ttl");
-static CAP: usize = 100; // maximum number of values shown per property
-static SKOLEM_START: &str = ".well-known/genid/";
+static CAP: usize = 100; // maximum number of values shown per property, also the page size of further values
+pub static SKOLEM_START: &str = ".well-known/genid/";
 
 type PrefixItem = (Prefix<Box<str>>, Iri<Box<str>>);
+/// Title and main type URI of a resource, if it has them.
+type TitleType = (Option<String>, Option<String>);
+
+/// Escape text from the knowledge base for use in HTML element content and attribute values.
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    tinytemplate::escape(s, &mut escaped);
+    escaped
+}
+
+/// Whether the IRI can be a link target, which excludes schemes other than HTTP(S), such as `javascript:`, that would run or open something else when clicked.
+/// Relative references are allowed.
+pub fn linkable(iri: &str) -> bool {
+    match iri.split_once(':') {
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https"),
+        _ => true,
+    }
+}
 
 // Prefixed IRI
 pub struct Piri {
@@ -53,20 +85,27 @@ impl Piri {
     pub fn new(iri: Iri<&str>) -> Self {
         Self { prefixed: prefixes().get_prefixed_pair(iri).map(|(p, ms)| (p.to_string(), String::from(ms))), full: iri.as_str().to_owned() }
     }
-    fn embrace(&self) -> String { format!("&lt;{self}&gt;") }
+    fn embrace(&self) -> String { format!("&lt;{}&gt;", escape(&self.full)) }
+    /// HTML representation, escaped.
     fn prefixed_string(&self, bold: bool, embrace: bool) -> String {
         if let Some((p, s)) = &self.prefixed {
+            let (p, s) = (escape(p), escape(s));
             if bold { format!("{p}:<b>{s}</b>") } else { format!("{p}:{s}") }
         } else if embrace {
             self.embrace()
         } else {
-            self.to_string()
+            escape(&self.full)
         }
     }
+    /// Prefixed IRI if possible, otherwise the full IRI, escaped for HTML.
     pub fn short(&self) -> String { self.prefixed_string(false, false) }
     pub fn suffix(&self) -> String { self.prefixed.as_ref().map_or_else(|| self.full.clone(), |pair| pair.1.clone()) }
     pub fn root_relative(&self) -> String { self.full.replace(config().namespace.as_str(), &(config().base.clone() + "/")) }
-    fn property_anchor(&self) -> String { format!("<a href='{}'>{}</a>", self.root_relative(), self.prefixed_string(true, false)) }
+    /// Link to the resource around the given HTML, only the HTML if the IRI is not [linkable].
+    pub fn anchor(&self, html: &str, attributes: &str) -> String {
+        if linkable(&self.full) { format!("<a href='{}'{attributes}>{html}</a>", escape(&self.root_relative())) } else { html.to_owned() }
+    }
+    fn property_anchor(&self) -> String { self.anchor(&self.prefixed_string(true, false), "") }
 }
 
 impl<T: std::borrow::Borrow<str>> From<&IriRef<T>> for Piri {
@@ -80,13 +119,15 @@ impl From<IriRef<&str>> for Piri {
 // Graph cannot be made into a trait object as of Rust 1.67 and Sophia 0.7, see https://github.com/pchampin/sophia_rs/issues/122.
 // Enum is cumbersome but we don't have a choice.
 // There may be a more elegant way in future Rust and Sophia versions.
-#[allow(clippy::large_enum_variant)]
+#[allow(clippy::large_enum_variant, clippy::enum_variant_names)]
 pub enum GraphEnum {
     // Sophia: "A heavily indexed graph. Fast to query but slow to load, with a relatively high memory footprint.".
     // Alternatively, use LightGraph, see <https://docs.rs/sophia/latest/sophia/graph/inmem/type.LightGraph.html>.
     FastGraph(FastGraph),
     #[cfg(feature = "hdt")]
     HdtGraph(Hdt),
+    /// Remote SPARQL endpoint that is queried instead of loading a knowledge base, see `endpoint_backend` in the config.
+    EndpointGraph(EndpointGraph),
 }
 
 impl GraphEnum {
@@ -101,11 +142,13 @@ impl GraphEnum {
             GraphEnum::FastGraph(g) => Box::new(g.triples_matching(sm, pm, om).flatten().map(|triple| Ok(triple.map(SimpleTerm::from_term)))),
             #[cfg(feature = "hdt")]
             GraphEnum::HdtGraph(g) => Box::new(g.triples_matching(sm, pm, om).flatten().map(|triple| Ok(triple.map(SimpleTerm::from_term)))),
+            // errors are logged by the endpoint graph
+            GraphEnum::EndpointGraph(g) => Box::new(g.triples_matching(sm, pm, om).map(Ok)),
         }
     }
 }
 
-pub fn kb_reader(filename: &str) -> anyhow::Result<BufReader<impl std::io::Read>> {
+pub fn kb_reader(filename: &str) -> anyhow::Result<BufReader<impl std::io::Read + 'static>> {
     Ok(BufReader::new(if filename.starts_with("http") {
         Box::new(ureq::get(filename).call()?.into_body().into_reader()) as Box<dyn std::io::Read>
     } else {
@@ -113,24 +156,120 @@ pub fn kb_reader(filename: &str) -> anyhow::Result<BufReader<impl std::io::Read>
     }))
 }
 
-fn load_dir(g: &mut FastGraph, filename: &str) -> anyhow::Result<usize> {
-    info!("Loading all .ttl and .nt files from directory {filename}");
+/// Whether the triple is in at least one named graph of the dataset.
+fn in_named_graph<T: Triple>(named: &FastDataset, t: &T) -> bool {
+    named.quads_matching([t.s()], [t.p()], [t.o()], Any).filter_map(Result::ok).any(|q| q.g().is_some())
+}
+
+/// Insert the triples of all quads into the graph and additionally store those in a named graph in the dataset.
+/// If a triple is both in the default graph and in a named graph, it is also stored in the default graph of the dataset, see [`Kb::in_default_graph`].
+/// Returns the number of triples added to the graph.
+fn insert_quads(g: &mut FastGraph, named: &mut FastDataset, mut quads: impl QuadSource) -> anyhow::Result<usize> {
+    let mut count = 0;
+    quads
+        .try_for_each_quad(|q| -> Result<(), TermIndexFullError> {
+            let new = g.insert(q.s(), q.p(), q.o())?;
+            if new {
+                count += 1;
+            }
+            let t = [q.s(), q.p(), q.o()];
+            if q.g().is_some() {
+                // an existing triple that is not in a named graph yet comes from the default graph
+                if !new && !in_named_graph(named, &t) {
+                    named.insert(q.s(), q.p(), q.o(), None as Option<&SimpleTerm<'_>>)?;
+                }
+                named.insert(q.s(), q.p(), q.o(), q.g())?;
+            } else if !new && in_named_graph(named, &t) {
+                named.insert(q.s(), q.p(), q.o(), None as Option<&SimpleTerm<'_>>)?;
+            }
+            Ok(())
+        })
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    Ok(count)
+}
+
+/// Insert triples into the graph like [`FastGraph::insert_all`], which are all in the default graph, see [`insert_quads`].
+fn insert_triples(g: &mut FastGraph, named: &mut FastDataset, mut triples: impl TripleSource) -> anyhow::Result<usize> {
+    let mut count = 0;
+    triples
+        .try_for_each_triple(|t| -> Result<(), TermIndexFullError> {
+            if g.insert(t.s(), t.p(), t.o())? {
+                count += 1;
+            } else if in_named_graph(named, &t) {
+                named.insert(t.s(), t.p(), t.o(), None as Option<&SimpleTerm<'_>>)?;
+            }
+            Ok(())
+        })
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    Ok(count)
+}
+
+#[cfg(feature = "jsonld")]
+/// Parse JSON-LD including named graphs. Remote contexts are not loaded.
+/// The parser blocks on its own async runtime, which cannot be started inside the Actix runtime, so it runs in a separate thread.
+fn insert_jsonld(g: &mut FastGraph, named: &mut FastDataset, mut br: impl BufRead) -> anyhow::Result<usize> {
+    let mut s = String::new();
+    br.read_to_string(&mut s).context("Failed to read JSON-LD")?;
+    std::thread::scope(|scope| scope.spawn(|| insert_quads(g, named, jsonld::parser::parse_str(&s))).join())
+        .map_err(|_| anyhow::anyhow!("JSON-LD parser panicked"))?
+}
+
+/// Split off a compression suffix from a file name or URL, for example "kb.ttl.gz" -> ("kb.ttl", Some("gz")).
+fn split_compression(filename: &str) -> (&str, Option<&str>) {
+    match filename.rsplit_once('.') {
+        Some((inner, compression @ ("gz" | "bz2" | "zst" | "xz"))) => (inner, Some(compression)),
+        _ => (filename, None),
+    }
+}
+
+/// Wrap a reader into a streaming decompressor for the given compression suffix.
+fn decompress(br: impl BufRead + 'static, compression: Option<&str>) -> anyhow::Result<Box<dyn BufRead>> {
+    Ok(match compression {
+        None => Box::new(br),
+        Some("zst") => Box::new(BufReader::new(Decoder::with_buffer(br).context("Failed to create zstd decoder")?)),
+        #[cfg(feature = "compression")]
+        Some("gz") => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(br))),
+        #[cfg(feature = "compression")]
+        Some("bz2") => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(br))),
+        #[cfg(feature = "compression")]
+        Some("xz") => Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(br))),
+        Some(c) => return Err(anyhow::anyhow!("Cannot decompress \"{c}\": RickView was compiled without the \"compression\" feature.")),
+    })
+}
+
+/// Parse an RDF serialization given by its file extension into the graph, the default for no extension is RDF/XML.
+/// Quads from N-Quads, `TriG` and JSON-LD files are additionally stored in `named` to keep track of their graph name.
+/// Returns the number of triples added to the graph.
+fn parse(g: &mut FastGraph, named: &mut FastDataset, ext: Option<&str>, br: impl BufRead) -> anyhow::Result<usize> {
+    match ext {
+        Some("ttl") => insert_triples(g, named, turtle::parse_bufread(br)).context("Failed to parse Turtle"),
+        Some("nq") => insert_quads(g, named, nq::parse_bufread(br)).context("Failed to parse N-Quads"),
+        Some("trig") => insert_quads(g, named, trig::parse_bufread(br)).context("Failed to parse TriG"),
+        #[cfg(feature = "jsonld")]
+        Some("jsonld") => insert_jsonld(g, named, br).context("Failed to parse JSON-LD"),
+        // error types not compatible
+        #[cfg(feature = "rdfxml")]
+        Some("rdf" | "owl") | None => insert_triples(g, named, xml::parser::parse_bufread(br)).context("Failed to parse RDF/XML"),
+        #[allow(unreachable_patterns)] // None in case feature "rdfxml" is not active
+        Some("nt") | None => insert_triples(g, named, nt::parse_bufread(br)).context("Failed to parse N-Triples"),
+        Some(ext) => Err(anyhow::anyhow!("Unknown extension: \"{ext}\": cannot parse knowledge graph.")),
+    }
+}
+
+fn load_dir(g: &mut FastGraph, named: &mut FastDataset, filename: &str) -> anyhow::Result<usize> {
+    info!("Loading all .ttl, .nt, .nq, .trig, .jsonld, .rdf and .owl files, optionally compressed, from directory {filename}");
     std::fs::read_dir(filename)
         .context("Failed to read directory")?
         .flatten()
         .map(|e| -> anyhow::Result<usize> {
             let p = e.path();
+            let name = p.file_name().and_then(std::ffi::OsStr::to_str).unwrap_or_default();
+            let (inner, compression) = split_compression(name);
 
-            match p.extension().and_then(std::ffi::OsStr::to_str) {
-                Some(ext @ ("ttl" | "nt" | "rdf" | "owl")) => {
-                    let br = BufReader::new(File::open(&p).context("Error opening file")?);
-                    match ext {
-                        "ttl" => g.insert_all(turtle::parse_bufread(br)).context("Error parsing Turtle"),
-                        "nt" => g.insert_all(nt::parse_bufread(br)).context("Error loading N-Triples"),
-                        #[cfg(feature = "rdfxml")]
-                        "rdf" | "owl" => g.insert_all(xml::parser::parse_bufread(br)).context("Error loading RDF/XML"),
-                        _ => Ok(0),
-                    }
+            match Path::new(inner).extension().and_then(std::ffi::OsStr::to_str) {
+                Some(ext @ ("ttl" | "nt" | "nq" | "trig" | "jsonld" | "rdf" | "owl")) => {
+                    let br = decompress(BufReader::new(File::open(&p).context("Error opening file")?), compression)?;
+                    parse(g, named, Some(ext), br)
                 }
                 .context("Failed to parse file"),
                 _ => Ok(0),
@@ -140,86 +279,258 @@ fn load_dir(g: &mut FastGraph, filename: &str) -> anyhow::Result<usize> {
         .sum()
 }
 
-fn load_graph() -> anyhow::Result<GraphEnum> {
-    //let load = || -> anyhow::Result<(FastGraph, usize)> {
+/// Result of loading a single knowledge base file, directory or URL.
+#[allow(clippy::large_enum_variant)]
+enum Source {
+    /// Number of triples added to the graph.
+    Triples(usize),
+    /// HDT is kept as is if it is the only source.
+    #[cfg(feature = "hdt")]
+    Hdt(Hdt),
+}
+
+/// Load a single knowledge base file, directory or URL into the graph, decompressing it first if it ends with a compression suffix.
+/// Quads from N-Quads, `TriG` and JSON-LD files are additionally stored in `named` to keep track of their graph name.
+fn load_source(g: &mut FastGraph, named: &mut FastDataset, filename: &str) -> anyhow::Result<Source> {
+    if std::fs::metadata(filename).is_ok_and(|m| m.is_dir()) {
+        return Ok(Source::Triples(load_dir(g, named, filename)?));
+    }
+    #[cfg(feature = "hdt")]
+    let t = Instant::now();
+    let (inner, compression) = split_compression(filename);
+    let br = decompress(kb_reader(filename).context("Cannot read from file or URL.")?, compression)?;
+    let num_triples = match Path::new(inner).extension().and_then(std::ffi::OsStr::to_str) {
+        #[cfg(feature = "hdt")]
+        Some("hdt") => {
+            let hdt = Hdt::read(br).context("Failed to load HDT")?;
+            info!("Loaded HDT from {filename} in {:?}", t.elapsed());
+            return Ok(Source::Hdt(hdt));
+        }
+        ext => parse(g, named, ext, br),
+    }
+    .with_context(|| format!("Failed to load file '{filename}'"))?;
+    Ok(Source::Triples(num_triples))
+}
+
+/// Human readable list of the configured knowledge base sources for log messages.
+fn kb_names() -> String {
+    if config().endpoint_backend {
+        return format!("SPARQL endpoint {}", config().endpoint.as_deref().unwrap_or_default());
+    }
+    if config().kb_file.is_empty() { "example kb".to_owned() } else { config().kb_file.join(", ") }
+}
+
+/// Load and merge all configured knowledge base sources and count the triples that each source adds to the graph.
+/// A single HDT source is used directly, otherwise all sources are merged into a `FastGraph`.
+fn load_graph(named: &mut FastDataset, sources: &mut Vec<(String, usize)>) -> anyhow::Result<GraphEnum> {
     let mut g = FastGraph::new();
     let t = Instant::now();
-    let num_triples = match &config().kb_file {
-        None => {
-            warn!("No knowledge base configured. Loading example knowledge base. Set kb_file in data/config.toml or env var RICKVIEW_KB_FILE.");
-            g.insert_all(turtle::parse_str(EXAMPLE_KB)).context("Failed to parse example knowledge base")?
-        }
-        Some(filename) if std::fs::metadata(filename).is_ok_and(|m| m.is_dir()) => load_dir(&mut g, filename)?,
-        Some(filename) => {
-            let br = kb_reader(filename).context("Cannot read from file or URL.")?;
-            match Path::new(&filename).extension().and_then(std::ffi::OsStr::to_str) {
-                Some("ttl") => g.insert_all(turtle::parse_bufread(br)).context("Failed to parse Turtle"),
-                // error types not compatible
-                #[cfg(feature = "rdfxml")]
-                Some("rdf" | "owl") | None => g.insert_all(xml::parser::parse_bufread(br)).context("Failed to parse RDF/XML"),
-                #[allow(unreachable_patterns)] // None in case feature "rdfxml" is not active
-                Some("nt") | None => g.insert_all(nt::parse_bufread(br)).context("Failed to parse N-Triples"),
-                #[cfg(feature = "hdt")]
-                Some("zst") if filename.ends_with("hdt.zst") => {
-                    let decoder = Decoder::with_buffer(br).context("Failed to create zstd decoder")?;
-                    let hdt = hdt::Hdt::read(BufReader::new(decoder)).context("Failed to load HDT")?;
-                    info!("Decompressed and loaded HDT from {filename} in {:?}", t.elapsed());
-                    return Ok(GraphEnum::HdtGraph(hdt));
-                }
-                #[cfg(feature = "hdt")]
-                Some("hdt") => {
-                    let hdt = Hdt::read(br).context("Failed to load HDT")?;
-                    info!("Loaded HDT from {filename} in {:?}", t.elapsed());
+    if config().kb_file.is_empty() {
+        warn!("No knowledge base configured. Loading example knowledge base. Set kb_file in data/config.toml or env var RICKVIEW_KB_FILE.");
+        let num_triples = g.insert_all(turtle::parse_str(EXAMPLE_KB)).context("Failed to parse example knowledge base")?;
+        sources.push(("example kb".to_owned(), num_triples));
+    }
+    for (i, filename) in config().kb_file.iter().enumerate() {
+        progress(format!("loading {filename} ({}/{})", i + 1, config().kb_file.len()));
+        #[cfg(not(feature = "hdt"))]
+        let Source::Triples(num_triples) = load_source(&mut g, named, filename)?;
+        #[cfg(feature = "hdt")]
+        let num_triples = match load_source(&mut g, named, filename)? {
+            Source::Triples(num_triples) => num_triples,
+            Source::Hdt(hdt) => {
+                let num_triples = hdt.triples.adjlist_z.sequence.entries;
+                if config().kb_file.len() == 1 {
+                    sources.push((filename.clone(), num_triples));
                     return Ok(GraphEnum::HdtGraph(hdt));
                 }
-                Some(ext) => Err(anyhow::anyhow!("Unknown extension: \"{ext}\": cannot parse knowledge graph.")),
+                warn!("Converting HDT from {filename} to FastGraph to merge it with the other sources, which needs much more memory.");
+                insert_triples(&mut g, named, hdt.triples()).context("Failed to convert HDT to FastGraph")?
             }
-            .with_context(|| format!("Failed to load file '{filename}'"))?
-        }
-    };
+        };
+        info!("Loaded {num_triples} new triples from {filename}");
+        sources.push((filename.clone(), num_triples));
+    }
+    let num_triples: usize = sources.iter().map(|(_, n)| n).sum();
     if num_triples == 0 {
         return Err(anyhow::anyhow!("Graph is empty"));
     }
     if log_enabled!(Level::Info) {
-        info!("Loaded {} FastGraph triples from {} in {:?}", num_triples, &config().kb_file.as_deref().unwrap_or("example kb"), t.elapsed());
+        info!("Loaded {} FastGraph triples from {} in {:?}", num_triples, kb_names(), t.elapsed());
     }
     Ok(GraphEnum::FastGraph(g))
 }
 
-/// Load RDF graph from the RDF Turtle file specified in the config.
-pub fn graph() -> &'static GraphEnum {
-    //let (g, num_triples) = load()
-    GRAPH.get_or_init(|| {
-        load_graph().unwrap_or_else(|e| {
-            error!("Fatal error loading graph from {}: {e:?}", &config().kb_file.as_deref().unwrap_or("example kb"));
-            std::process::exit(1);
-        })
-    })
+/// The knowledge base together with information gathered while loading it and the indexes derived from it.
+/// Replaced as a whole when the knowledge base is reloaded.
+pub struct Kb {
+    pub graph: GraphEnum,
+    /// Quads from N-Quads, `TriG` and JSON-LD files that are in a named graph, if there are any.
+    named: Option<FastDataset>,
+    /// Knowledge base sources with the number of triples that each of them added to the graph, in the order of `kb_file`.
+    pub sources: Vec<(String, usize)>,
+    /// How long it took to load the graph, see [`load_graph`].
+    pub load_time: Duration,
+    /// Key of the index cache file, created before loading the knowledge base. None if the index cache is disabled.
+    index_key: Option<index_cache::Key>,
+    /// Result of [`Kb::titles`] and [`Kb::types`].
+    indexes: OnceLock<Indexes>,
+    /// Result of [`Kb::search_index`].
+    search: OnceLock<search::Index>,
+    /// Title and type by resource URI, looked up on demand when the indexes are disabled, see [`Kb::title`].
+    lookups: Mutex<LruCache<String, TitleType>>,
 }
 
-/// (prefix,iri) pairs from the config
-fn prefixes() -> &'static Vec<PrefixItem> {
-    PREFIXES.get_or_init(|| {
-        let mut p: Vec<PrefixItem> = Vec::new();
-        for (prefix, iri) in &config().namespaces {
-            p.push((Prefix::new_unchecked(prefix.clone()), Iri::new_unchecked(iri.clone())));
+impl Kb {
+    fn load() -> anyhow::Result<Kb> {
+        if config().endpoint_backend {
+            let endpoint = config().endpoint.clone().context("endpoint_backend is enabled but no endpoint is configured")?;
+            info!("Using SPARQL endpoint {endpoint} as knowledge base");
+            let graph = GraphEnum::EndpointGraph(EndpointGraph::new(endpoint));
+            return Ok(Kb::new(graph, None, Vec::new(), Duration::ZERO, None));
         }
-        p.push((Prefix::new_unchecked(config().prefix.clone()), config().namespace.clone()));
-        p
-    })
-}
+        let index_key = index_cache::Key::new();
+        let mut named = FastDataset::new();
+        let mut sources = Vec::new();
+        let t = Instant::now();
+        let graph = load_graph(&mut named, &mut sources)?;
+        let load_time = t.elapsed();
+        let named = if named.quads().next().is_some() { Some(named) } else { None };
+        Ok(Kb::new(graph, named, sources, load_time, index_key))
+    }
 
-/// Maps RDF resource URIs to at most one title each, for example `http://example.com/resource/ExampleResource` -> "example resource".
-/// Prioritizes `title_properties` earlier in the list.
-/// This is only run once to minimize the number of queries and generates the title for every resource in the graph.
-/// For very large graph this can take too much time or memory and can be disabled with setting the "large" config option to true.
-pub fn titles() -> &'static HashMap<String, String> {
-    TITLES.get_or_init(|| {
-        let mut titles = HashMap::<String, String>::new();
-        if config().large {
-            return titles;
+    fn new(graph: GraphEnum, named: Option<FastDataset>, sources: Vec<(String, usize)>, load_time: Duration, index_key: Option<index_cache::Key>) -> Kb {
+        let lookups = Mutex::new(LruCache::new(NonZeroUsize::new(config().lazy_cache_size).unwrap_or(NonZeroUsize::MIN)));
+        Kb { graph, named, sources, load_time, index_key, indexes: OnceLock::new(), search: OnceLock::new(), lookups }
+    }
+
+    /// Names of the named graphs that contain the given triple, either IRIs or blank nodes.
+    /// Empty if the triple is only in the default graph or no N-Quads or `TriG` file is loaded.
+    fn graph_names<T: Triple>(&self, t: &T) -> Vec<SimpleTerm<'static>> {
+        let Some(named) = &self.named else {
+            return Vec::new();
+        };
+        named.quads_matching([t.s()], [t.p()], [t.o()], Any).filter_map(Result::ok).filter_map(|q| q.g().map(SimpleTerm::from_term)).collect()
+    }
+
+    /// Whether the triple is in the default graph, which includes all triples from files without named graphs.
+    fn in_default_graph<T: Triple>(&self, t: &T) -> bool {
+        let Some(named) = &self.named else {
+            return true;
+        };
+        let mut quads = named.quads_matching([t.s()], [t.p()], [t.o()], Any).filter_map(Result::ok).peekable();
+        quads.peek().is_none() || quads.any(|q| q.g().is_none())
+    }
+
+    /// Maps RDF resource URIs to at most one title each, for example `http://example.com/resource/ExampleResource` -> "example resource".
+    /// Prioritizes `title_properties` earlier in the list.
+    /// This is only run once to minimize the number of queries and generates the title for every resource in the graph.
+    /// For very large graph this can take too much time or memory and can be disabled with setting the "large" config option to true,
+    /// unless `index_cache` is set, in which case it only needs to be generated once for the current knowledge base files.
+    /// Always disabled for a SPARQL endpoint.
+    pub fn titles(&self) -> &HashMap<String, String> { &self.indexes().titles }
+
+    /// Maps RDF resource suffixes to at most one type URI each, for example "`ExampleResource`" -> `http://example.com/resource/ExampleClass`.
+    /// Prioritizes `type_properties` earlier in the list.
+    /// Disabled like [`Kb::titles`].
+    pub fn types(&self) -> &HashMap<String, String> { &self.indexes().types }
+
+    /// Whether [`Kb::titles`] and [`Kb::types`] are generated, see there.
+    fn indexed(&self) -> bool { !(matches!(self.graph, GraphEnum::EndpointGraph(_)) || config().large && self.index_key.is_none()) }
+
+    /// Search index over the titles and descriptions, built on first use.
+    /// None if the titles are not indexed, see [`Kb::titles`].
+    pub fn search_index(&self) -> Option<&search::Index> {
+        if !self.indexed() {
+            return None;
+        }
+        Some(self.search.get_or_init(|| search::Index::new(self)))
+    }
+
+    /// Title of the resource with the given URI, either from the title index or, if that is disabled and `lazy_titles` is set, looked up in the graph.
+    pub fn title(&self, uri: &str) -> Option<String> {
+        if self.indexed() {
+            self.titles().get(uri).cloned()
+        } else if config().lazy_titles {
+            self.lookup(uri).0
+        } else {
+            None
+        }
+    }
+
+    /// Main type URI of the resource with the given URI, either from the type index or looked up like [`Kb::title`].
+    pub fn main_type(&self, uri: &str) -> Option<String> {
+        if self.indexed() {
+            self.types().get(&uri.replace(config().namespace.as_str(), "")).cloned()
+        } else if config().lazy_titles {
+            self.lookup(uri).1
+        } else {
+            None
+        }
+    }
+
+    /// Title and main type of a single resource, prioritized in the same way as the indexes and cached in a bounded LRU cache.
+    fn lookup(&self, uri: &str) -> TitleType {
+        if let Some(cached) = self.lookups.lock().unwrap_or_else(PoisonError::into_inner).get(uri) {
+            return cached.clone();
         }
-        let g = graph();
+        let Ok(iref) = IriRef::new(uri.to_owned().into()) else {
+            return (None, None);
+        };
+        // ((language position, title property position), title)
+        let mut title: Option<((usize, usize), String)> = None;
+        // (type property position, type)
+        let mut main_type: Option<(usize, String)> = None;
+        // one query for all properties, which a SPARQL endpoint also needs for the resource page
+        for t in self.graph.triples_matching([SimpleTerm::Iri(iref)], Any, Any).flatten() {
+            let Some(p) = t.p().iri() else {
+                continue;
+            };
+            if let Some(i) = config().title_properties.iter().position(|x| x == p.as_str()) {
+                let (lit, tag) = match t.o() {
+                    SimpleTerm::LiteralLanguage(lit, tag) => (lit, tag.as_str()),
+                    SimpleTerm::LiteralDatatype(lit, _) => (lit, ""),
+                    _ => continue,
+                };
+                let rank = (config().langs.iter().position(|x| x == tag).unwrap_or(1000), i);
+                if title.as_ref().is_none_or(|(best, _)| rank < *best) {
+                    title = Some((rank, lit.to_string()));
+                }
+            }
+            if let Some(i) = config().type_properties.iter().position(|x| x == p.as_str())
+                && let SimpleTerm::Iri(o) = t.o()
+                && main_type.as_ref().is_none_or(|(best, _)| i < *best)
+            {
+                main_type = Some((i, o.as_str().to_owned()));
+            }
+        }
+        let result = (title.map(|(_, title)| title), main_type.map(|(_, main_type)| main_type));
+        self.lookups.lock().unwrap_or_else(PoisonError::into_inner).put(uri.to_owned(), result.clone());
+        result
+    }
+
+    /// Titles and types, from the index cache if it is up to date, otherwise generated and saved to the index cache.
+    fn indexes(&self) -> &Indexes {
+        self.indexes.get_or_init(|| {
+            let key = self.index_key.as_ref();
+            if !self.indexed() {
+                return Indexes::default();
+            }
+            if let Some(indexes) = key.and_then(index_cache::load) {
+                return indexes;
+            }
+            let t = Instant::now();
+            let indexes = Indexes { titles: self.generate_titles(), types: self.generate_types() };
+            info!("Generated {} titles and {} types in {:?}", indexes.titles.len(), indexes.types.len(), t.elapsed());
+            if let Some(key) = key {
+                index_cache::save(key, &indexes);
+            }
+            indexes
+        })
+    }
+
+    fn generate_titles(&self) -> HashMap<String, String> {
+        let mut titles = HashMap::<String, String>::new();
+        let g = &self.graph;
         // tag, uri, title
         let mut tagged = MultiMap::<String, (String, String)>::new();
         for prop in config().title_properties.iter().rev() {
@@ -257,17 +568,10 @@ pub fn titles() -> &'static HashMap<String, String> {
             }
         }
         titles
-    })
-}
+    }
 
-/// Maps RDF resource suffixes to at most one type URI each, for example "`ExampleResource`" -> `http://example.com/resource/ExampleClass`.
-/// Prioritizes `type_properties` earlier in the list.
-pub fn types() -> &'static HashMap<String, String> {
-    TYPES.get_or_init(|| {
+    fn generate_types(&self) -> HashMap<String, String> {
         let mut types = HashMap::<String, String>::new();
-        if config().large {
-            return types;
-        }
         for prop in config().type_properties.iter().rev() {
             let iref = IriRef::new(prop.clone().into());
             if iref.is_err() {
@@ -275,7 +579,7 @@ pub fn types() -> &'static HashMap<String, String> {
                 continue;
             }
             let term = SimpleTerm::Iri(iref.unwrap());
-            for tt in graph().triples_matching(Any, Some(term), Any) {
+            for tt in self.graph.triples_matching(Any, Some(term), Any) {
                 let t = tt.expect("error fetching type triple");
                 if !t.s().is_iri() {
                     continue;
@@ -292,16 +596,151 @@ pub fn types() -> &'static HashMap<String, String> {
             }
         }
         types
+    }
+}
+
+/// Loading state of the knowledge base, see [`status`].
+#[derive(Clone, Debug)]
+pub enum Status {
+    /// Loading starts on the first request that needs the knowledge base, unless `preload` is enabled.
+    NotLoaded,
+    /// current step and when loading started
+    Loading(String, Instant),
+    Loaded,
+    /// error message of the failed initial load, cleared by a successful [reload]
+    Failed(String),
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::NotLoaded => write!(f, "Knowledge base not loaded yet"),
+            Status::Loading(step, since) => write!(f, "Loading knowledge base for {:.0?}: {step}", since.elapsed()),
+            Status::Loaded => write!(f, "Knowledge base loaded"),
+            Status::Failed(e) => write!(f, "Failed to load knowledge base: {e}"),
+        }
+    }
+}
+
+/// Current loading state of the knowledge base.
+pub fn status() -> Status { STATUS.lock().unwrap_or_else(PoisonError::into_inner).clone() }
+
+fn set_status(status: Status) { *STATUS.lock().unwrap_or_else(PoisonError::into_inner) = status; }
+
+/// Update the current step while the knowledge base is initially loaded, which is ignored during a reload.
+fn progress(step: String) {
+    if let Status::Loading(current, _) = &mut *STATUS.lock().unwrap_or_else(PoisonError::into_inner) {
+        debug!("{step}");
+        *current = step;
+    }
+}
+
+/// The knowledge base if it is already loaded.
+pub fn loaded() -> Option<Arc<Kb>> { KB.read().unwrap_or_else(PoisonError::into_inner).clone() }
+
+/// Load the knowledge base from the files specified in the config and compute the titles and types unless it is already loaded.
+/// Blocks until loading is finished, also if another thread is loading it already.
+/// A failed load is not repeated, only a [reload] tries again.
+pub fn load() -> anyhow::Result<Arc<Kb>> {
+    if let Some(kb) = loaded() {
+        return Ok(kb);
+    }
+    let _guard = LOAD.lock().unwrap_or_else(PoisonError::into_inner);
+    // another thread may have loaded it while we were waiting for the lock
+    if let Some(kb) = loaded() {
+        return Ok(kb);
+    }
+    if let Status::Failed(e) = status() {
+        return Err(anyhow::anyhow!(e));
+    }
+    let t = Instant::now();
+    set_status(Status::Loading(format!("loading {}", kb_names()), t));
+    let kb = Kb::load().map(|kb| {
+        progress("indexing titles and types".to_owned());
+        kb.titles();
+        kb.types();
+        Arc::new(kb)
+    });
+    match kb {
+        Ok(kb) => {
+            *KB.write().unwrap_or_else(PoisonError::into_inner) = Some(kb.clone());
+            set_status(Status::Loaded);
+            info!("Knowledge base ready after {:?}", t.elapsed());
+            Ok(kb)
+        }
+        Err(e) => {
+            error!("Error loading knowledge base from {}: {e:?}", kb_names());
+            set_status(Status::Failed(format!("{e:#}")));
+            Err(e)
+        }
+    }
+}
+
+/// Start loading the knowledge base in a background thread unless loading has already started.
+pub fn load_in_background() {
+    let mut status = STATUS.lock().unwrap_or_else(PoisonError::into_inner);
+    if matches!(*status, Status::NotLoaded) {
+        *status = Status::Loading(format!("loading {}", kb_names()), Instant::now());
+        std::thread::spawn(|| load().ok());
+    }
+}
+
+/// The current knowledge base, loaded from the files specified in the config on first access, see [load].
+/// Callers keep using the knowledge base they got even if it is replaced in the meantime by [reload].
+/// # Panics
+/// Panics if the knowledge base could not be loaded. The server only handles requests that need the knowledge base after it has been loaded.
+pub fn kb() -> Arc<Kb> { load().unwrap_or_else(|e| panic!("Knowledge base is not available: {e:?}")) }
+
+/// Permission to reload the knowledge base, which only one holder at a time can get so that reloads don't run concurrently.
+/// Can be acquired before starting the reload in a background thread, for example to tell a client that its request was dropped.
+pub struct ReloadGuard(());
+
+impl ReloadGuard {
+    /// None if another reload is still running.
+    pub fn acquire() -> Option<ReloadGuard> { RELOADING.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).ok().map(|_| ReloadGuard(())) }
+}
+
+impl Drop for ReloadGuard {
+    fn drop(&mut self) { RELOADING.store(false, Ordering::Release); }
+}
+
+/// Load the knowledge base again from the files specified in the config, compute the titles and types and then replace the current one.
+/// Blocks until the reload is finished, so call this from a background thread.
+/// Returns an error and keeps the current knowledge base if loading fails.
+pub fn reload(_guard: &ReloadGuard) -> anyhow::Result<()> {
+    // waits for the initial load, whose result would otherwise replace the reloaded knowledge base
+    let _load = LOAD.lock().unwrap_or_else(PoisonError::into_inner);
+    let t = Instant::now();
+    info!("Reloading knowledge base from {}", kb_names());
+    let kb = Kb::load().with_context(|| format!("Failed to reload knowledge base from {}", kb_names()))?;
+    kb.titles();
+    kb.types();
+    *KB.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(kb));
+    set_status(Status::Loaded);
+    info!("Reloaded knowledge base in {:?}", t.elapsed());
+    Ok(())
+}
+
+/// (prefix,iri) pairs from the config
+fn prefixes() -> &'static Vec<PrefixItem> {
+    PREFIXES.get_or_init(|| {
+        let mut p: Vec<PrefixItem> = Vec::new();
+        for (prefix, iri) in &config().namespaces {
+            p.push((Prefix::new_unchecked(prefix.clone()), Iri::new_unchecked(iri.clone())));
+        }
+        p.push((Prefix::new_unchecked(config().prefix.clone()), config().namespace.clone()));
+        p
     })
 }
 
-/// Contains the knowledge base.
-static GRAPH: OnceLock<GraphEnum> = OnceLock::new();
+/// Contains the knowledge base, see [kb].
+static KB: RwLock<Option<Arc<Kb>>> = RwLock::new(None);
+/// Held during [load] and [reload] so that other threads wait for the knowledge base instead of loading it again at the same time.
+static LOAD: Mutex<()> = Mutex::new(());
+static STATUS: Mutex<Status> = Mutex::new(Status::NotLoaded);
+/// Set while a [`ReloadGuard`] exists.
+static RELOADING: AtomicBool = AtomicBool::new(false);
 static PREFIXES: OnceLock<Vec<PrefixItem>> = OnceLock::new();
-/// Map of RDF resource suffixes to at most one title each.
-static TITLES: OnceLock<HashMap<String, String>> = OnceLock::new();
-/// Map of RDF resource suffixes to at most one type URI each. Result of [types].
-static TYPES: OnceLock<HashMap<String, String>> = OnceLock::new();
 
 /// Whether the given resource is in subject or object position.
 enum PropertyType {
@@ -313,10 +752,12 @@ enum PropertyType {
 struct Property {
     prop_html: String,
     target_htmls: Vec<String>,
+    /// total number of values, of which at most CAP are in `target_htmls`
+    count: usize,
 }
 
-impl From<Property> for (String, Vec<String>) {
-    fn from(p: Property) -> Self { (p.prop_html, p.target_htmls) }
+impl From<Property> for (String, Vec<String>, Option<usize>) {
+    fn from(p: Property) -> Self { (p.prop_html, p.target_htmls, Some(p.count).filter(|&c| c > 1)) }
 }
 
 /// Map skolemized IRIs back to blank nodes. Keep deskolemized IRIs as they are.
@@ -325,9 +766,6 @@ fn deskolemize<'a>(iri: &'a Iri<&str>) -> SimpleTerm<'a> {
 }
 
 fn blank_html(props: BTreeMap<String, Property>, depth: usize) -> String {
-    if depth > 9 {
-        return "...".to_owned();
-    }
     // temporary manchester syntax emulation
     if let Some(on_property) = props.get("http://www.w3.org/2002/07/owl#onProperty")
         && let Some(some) = props.get("http://www.w3.org/2002/07/owl#someValuesFrom")
@@ -350,120 +788,653 @@ fn blank_html(props: BTreeMap<String, Property>, depth: usize) -> String {
     format!("{indent}<table>{rows}{indent}</table>")
 }
 
-/// For a given resource r, get either all direct properties (p,o) where (r,p,o) is in the graph or indirect ones (s,p) where (s,p,r) is in the graph.
-fn properties(conn_type: &PropertyType, source: &SimpleTerm<'_>, depth: usize) -> BTreeMap<String, Property> {
-    let g = graph();
+/// Comma separated named graphs shown next to a property value.
+fn graph_html(names: &[SimpleTerm<'_>]) -> String {
+    names
+        .iter()
+        .map(|name| match name {
+            SimpleTerm::Iri(iri) => format!("<span title='named graph {}'>{}</span>", escape(iri.as_str()), Piri::from(iri.as_ref()).short()),
+            SimpleTerm::BlankNode(id) => format!("<span title='named graph'>_:{}</span>", escape(id.as_str())),
+            _ => escape(&format!("{name:?}")),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Percent-encode all bytes except ASCII letters, digits and the given characters.
+fn percent_encode(s: &str, keep: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || keep.contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push('%');
+            encoded.push(char::from(HEX[usize::from(b >> 4)]));
+            encoded.push(char::from(HEX[usize::from(b & 15)]));
+        }
+    }
+    encoded
+}
+
+/// Percent-encode a query parameter value, keeping only the characters that are valid in it and have no special meaning.
+pub fn encode_query_value(s: &str) -> String { percent_encode(s, b"-._~:/@!$'()*,;?") }
+
+/// Percent-encode a value like the form-style query expansion of URI templates, see <https://datatracker.ietf.org/doc/html/rfc6570#section-3.2.8>.
+pub fn encode_template_value(s: &str) -> String { percent_encode(s, b"-._~") }
+
+/// Percent-encode a URL path, keeping only the characters that are valid in it and have no special meaning.
+pub fn encode_path(s: &str) -> String { percent_encode(s, b"-._~:/@!$&'()*+,;=") }
+
+/// Link to the given page of values of a property, see [`property_page`].
+fn more_html(prop: &str, conn_type: &PropertyType, page: usize, remaining: usize) -> String {
+    let inverse = if matches!(conn_type, PropertyType::Inverse) { "&inverse=true" } else { "" };
+    let href = format!("?prop={}&page={page}{inverse}", encode_query_value(prop));
+    format!("<a class='more' href='{}'>{remaining} more</a>", escape(&href))
+}
+
+type TermTriple = [SimpleTerm<'static>; 3];
+
+/// For a given resource r, get either all direct triples (r,p,o) or indirect ones (s,p,r) grouped by property IRI.
+/// Only properties matching `pm` are included.
+/// The values of each property are in the order in which they are shown, see [`value_order`], without rendering them.
+fn property_triples<P: TermMatcher>(kb: &Kb, conn_type: &PropertyType, source: &SimpleTerm<'_>, pm: P) -> BTreeMap<String, Vec<TermTriple>> {
+    let g = &kb.graph;
     let triples = match conn_type {
-        PropertyType::Direct => g.triples_matching(Some(source), Any, Any),
-        PropertyType::Inverse => g.triples_matching(Any, Any, Some(source)),
+        PropertyType::Direct => g.triples_matching(Some(source), pm, Any),
+        PropertyType::Inverse => g.triples_matching(Any, pm, Some(source)),
     };
-    let mut map: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut map: BTreeMap<String, Vec<TermTriple>> = BTreeMap::new();
     for res in triples {
         let triple = res.expect("error with connection triple");
-        let target_term = match conn_type {
-            PropertyType::Direct => triple.o(),
-            PropertyType::Inverse => triple.s(),
-        };
-        let target_html = match target_term.as_simple() {
-            SimpleTerm::LiteralLanguage(lit, tag) => format!("{lit} @{}", tag.as_str()),
+        if let Some(prop) = triple.p().iri().map(|iri| iri.as_str().to_owned()) {
+            map.entry(prop).or_default().push(triple);
+        }
+    }
+    let target = if matches!(conn_type, PropertyType::Direct) { 2 } else { 0 };
+    for triples in map.values_mut() {
+        triples.sort_by_cached_key(|t| value_order(&t[target]));
+    }
+    map
+}
 
-            SimpleTerm::LiteralDatatype(lit, dt) => format!(r#"{lit}<div class="datatype">{}</div>"#, Piri::from(dt.as_ref()).short()),
+/// Sort key of property values: literals by lexical form, then IRIs and then blank nodes, which can be computed without looking up titles.
+fn value_order(term: &SimpleTerm<'_>) -> (u8, String) {
+    match term {
+        SimpleTerm::LiteralLanguage(lit, tag) => (0, format!("{lit}@{}", tag.as_str())),
+        SimpleTerm::LiteralDatatype(lit, dt) => (0, format!("{lit}^^{}", dt.as_str())),
+        SimpleTerm::Iri(iri) => (1, iri.as_str().to_owned()),
+        SimpleTerm::BlankNode(id) => (2, id.as_str().to_owned()),
+        _ => (3, format!("{term:?}")),
+    }
+}
 
-            SimpleTerm::Iri(iri) => {
-                let piri = Piri::from(iri.as_ref());
-                let title = if let Some(title) = titles().get(&piri.to_string()) { format!("<br><span>&#8618; {title}</span>") } else { String::new() };
-                let target = if piri.to_string().starts_with(config().namespace.as_str()) { "" } else { " target='_blank' " };
-                format!("<a href='{}'{target}>{}{title}</a>", piri.root_relative(), piri.prefixed_string(false, true))
+/// HTML of the value of a property, which is the object of a direct triple or the subject of an inverse one.
+fn value_html(kb: &Kb, conn_type: &PropertyType, triple: &TermTriple, depth: usize) -> String {
+    let target_term = match conn_type {
+        PropertyType::Direct => triple.o(),
+        PropertyType::Inverse => triple.s(),
+    };
+    let target_html = match target_term {
+        SimpleTerm::LiteralLanguage(lit, tag) => format!("{} @{}", escape(lit), escape(tag.as_str())),
+
+        SimpleTerm::LiteralDatatype(lit, dt) => format!(r#"{}<div class="datatype">{}</div>"#, escape(lit), Piri::from(dt.as_ref()).short()),
+
+        SimpleTerm::Iri(iri) => {
+            let piri = Piri::from(iri.as_ref());
+            let title = if let Some(title) = kb.title(&piri.to_string()) { format!("<br><span>&#8618; {}</span>", escape(&title)) } else { String::new() };
+            let target = if piri.to_string().starts_with(config().namespace.as_str()) { "" } else { " target='_blank' " };
+            piri.anchor(&format!("{}{title}", piri.prefixed_string(false, true)), target)
+        }
+        // https://www.w3.org/TR/rdf11-concepts/ Section 3.5 Replacing Blank Nodes with IRIs
+        SimpleTerm::BlankNode(blank) => {
+            let id = blank.as_str();
+            let sub_html = if !matches!(conn_type, PropertyType::Direct) {
+                String::new()
+            } else if depth > 9 {
+                // checked before collecting the nested properties, which would otherwise recurse endlessly on cycles of blank nodes
+                "...".to_owned()
+            } else {
+                blank_html(properties(kb, &PropertyType::Direct, target_term, depth + 1), depth)
+            };
+            let r = IriRef::new_unchecked(SKOLEM_START.to_owned() + id);
+            let iri = config().namespace.resolve(r);
+            //format!("<a href='{}'>_:{id}</a><br>&#8618;<p>{sub_html}</p>", Piri::new(iri.as_ref()).root_relative())
+            format!("&#8618;<a href='{}'> Blank Node {}</a>{sub_html}", escape(&Piri::new(iri.as_ref()).root_relative()), escape(id))
+        }
+        _ => escape(&format!("{target_term:?}")),
+    };
+    let names = kb.graph_names(triple);
+    if names.is_empty() { target_html } else { format!(r#"{target_html}<div class="graph">{}</div>"#, graph_html(&names)) }
+}
+
+/// For a given resource r, get the HTML of either all direct properties (p,o) where (r,p,o) is in the graph or indirect ones (s,p) where (s,p,r) is in the graph.
+/// Only the first CAP values of each property are rendered.
+/// On the resource page, the remaining values can be loaded page by page, see [`property_page`].
+fn properties(kb: &Kb, conn_type: &PropertyType, source: &SimpleTerm<'_>, depth: usize) -> BTreeMap<String, Property> {
+    property_triples(kb, conn_type, source, Any)
+        .into_iter()
+        .map(|(prop, triples)| {
+            let count = triples.len();
+            let mut target_htmls: Vec<String> = triples.iter().take(CAP).map(|t| value_html(kb, conn_type, t, depth)).collect();
+            if count > CAP {
+                // blank nodes don't have their own page
+                target_htmls.push(if depth == 0 { more_html(&prop, conn_type, 1, count - CAP) } else { "...".to_owned() });
             }
-            // https://www.w3.org/TR/rdf11-concepts/ Section 3.5 Replacing Blank Nodes with IRIs
-            SimpleTerm::BlankNode(blank) => {
-                let id = blank.as_str();
-                let sub_html = if matches!(conn_type, PropertyType::Direct) {
-                    blank_html(properties(&PropertyType::Direct, target_term, depth + 1), depth)
-                } else {
-                    String::new()
-                };
-                let r = IriRef::new_unchecked(SKOLEM_START.to_owned() + id);
-                let iri = config().namespace.resolve(r);
-                //format!("<a href='{}'>_:{id}</a><br>&#8618;<p>{sub_html}</p>", Piri::new(iri.as_ref()).root_relative())
-                format!("&#8618;<a href='{}'> Blank Node {id}</a>{sub_html}", Piri::new(iri.as_ref()).root_relative())
+            let prop_html = Piri::new(Iri::new_unchecked(&prop)).property_anchor();
+            (prop, Property { prop_html, target_htmls, count })
+        })
+        .collect()
+}
+
+/// HTML fragment with the values of the given property of a resource on the given page, where page 0 is shown on the resource page.
+/// Only the values on the page are rendered.
+/// Ends with a link to the next page if there are more values.
+/// None if the property is not a valid IRI.
+pub fn property_page(subject: Iri<&str>, prop: &str, inverse: bool, page: usize) -> Option<String> {
+    let kb = kb();
+    let conn_type = if inverse { PropertyType::Inverse } else { PropertyType::Direct };
+    let pm = SimpleTerm::Iri(IriRef::new(prop.to_owned().into()).ok()?);
+    let triples = property_triples(&kb, &conn_type, &deskolemize(&subject), Some(pm)).remove(prop).unwrap_or_default();
+    let count = triples.len();
+    let start = page.saturating_mul(CAP);
+    let mut spans: Vec<String> =
+        triples.iter().skip(start).take(CAP).map(|t| format!("<span class='c2'>{}</span>", value_html(&kb, &conn_type, t, 0))).collect();
+    if count > start.saturating_add(CAP) {
+        spans.push(format!("<span class='c2'>{}</span>", more_html(prop, &conn_type, page + 1, count - start - CAP)));
+    }
+    Some(spans.join(" "))
+}
+
+/// Triples that describe the resource according to `bounded_description`, see <https://www.w3.org/submission/CBD/>.
+/// The concise bounded description adds the triples of the blank nodes that are objects, recursively, so that for example OWL restrictions are complete.
+/// The symmetric one also adds the triples with the resource as object and the symmetric descriptions of blank nodes that are their subjects, at most `max_inverse_triples` of them.
+/// In every mode, the RDF lists of the included blank nodes are completed, see [`complete_lists`].
+fn description(kb: &Kb, source: &SimpleTerm<'_>) -> Vec<[SimpleTerm<'static>; 3]> {
+    // resources that are the object of many triples would otherwise have huge symmetric descriptions
+    let mut triples = bounded_description(kb, source, config().bounded_description, config().max_inverse_triples);
+    complete_lists(kb, &mut triples);
+    triples
+}
+
+/// The triples of the given bounded description with at most `inverse_limit` triples with the resource or its blank nodes as object, see [`description`].
+fn bounded_description(kb: &Kb, source: &SimpleTerm<'_>, mode: BoundedDescription, mut inverse_limit: usize) -> Vec<[SimpleTerm<'static>; 3]> {
+    if mode == BoundedDescription::Subject {
+        return kb.graph.triples_matching([source], Any, Any).flatten().collect();
+    }
+    let mut triples = Vec::new();
+    // blank nodes whose triples are already included, which also prevents cycles
+    let mut visited = BTreeSet::<String>::new();
+    let mut queue: Vec<SimpleTerm<'static>> = vec![source.into_term()];
+    let mut add = |term: &SimpleTerm<'static>, queue: &mut Vec<SimpleTerm<'static>>| {
+        if let Some(id) = term.bnode_id()
+            && !Term::eq(term, source)
+            && visited.insert(id.as_str().to_owned())
+        {
+            queue.push(term.clone());
+            return true;
+        }
+        false
+    };
+    while let Some(node) = queue.pop() {
+        for triple in kb.graph.triples_matching([&node], Any, Any).flatten() {
+            add(&triple[2], &mut queue);
+            triples.push(triple);
+        }
+        if mode == BoundedDescription::Symmetric {
+            let mut inverses = kb.graph.triples_matching(Any, Any, [&node]).flatten();
+            for triple in inverses.by_ref().take(inverse_limit) {
+                inverse_limit -= 1;
+                // triples of blank nodes and the resource are included when their subject is visited
+                if !add(&triple[0], &mut queue) && triple[0].bnode_id().is_none() && !Term::eq(&triple[0], source) {
+                    triples.push(triple);
+                }
             }
-            _ => format!("{target_term:?}"),
-        };
-        if let SimpleTerm::Iri(iri) = triple.p().as_simple() {
-            if let Some(values) = map.get_mut(iri.as_str()) {
-                values.insert(target_html);
-            } else {
-                let mut values = BTreeSet::new();
-                values.insert(target_html);
-                map.insert(iri.as_str().to_owned(), values);
+            if inverse_limit == 0 && inverses.next().is_some() {
+                debug!("Leaving out triples with {node:?} as object after max_inverse_triples");
             }
         }
     }
-    map.into_iter()
-        .map(|(prop, values)| {
-            let len = values.len();
-            let mut target_htmls: Vec<String> = values.into_iter().take(CAP).collect();
-            if len > CAP {
-                target_htmls.push("...".to_string());
+    triples
+}
+
+/// Whether the term is a blank node of an RDF list.
+fn is_list_node(kb: &Kb, term: &SimpleTerm<'_>) -> bool {
+    term.is_blank_node() && kb.graph.triples_matching([term], [rdf::rest], Any).next().is_some()
+}
+
+/// Add the complete RDF lists that the blank nodes of the triples belong to, because a part of a list is not a valid list, which the JSON-LD serializer cannot handle.
+/// For each list, these are the triples of all nodes from the head to the end, including nested lists, and the triples that refer to the head.
+fn complete_lists(kb: &Kb, triples: &mut Vec<TermTriple>) {
+    let id = |term: &SimpleTerm<'_>| term.bnode_id().map(|id| id.as_str().to_owned()).unwrap_or_default();
+    // blank nodes whose triples are all included
+    let mut described: BTreeSet<String> = triples.iter().filter(|t| t[0].is_blank_node()).map(|t| id(&t[0])).collect();
+    let mut heads = BTreeSet::<String>::new();
+    let mut queue: Vec<SimpleTerm<'static>> = triples.iter().flat_map(|t| [&t[0], &t[2]]).filter(|t| is_list_node(kb, t)).cloned().collect();
+    // add all triples of a blank node that are not included yet and queue the lists that it refers to
+    let mut describe = |node: &SimpleTerm<'static>, triples: &mut Vec<TermTriple>, queue: &mut Vec<SimpleTerm<'static>>| {
+        if !described.insert(id(node)) {
+            return false;
+        }
+        for triple in kb.graph.triples_matching([node], Any, Any).flatten() {
+            if is_list_node(kb, &triple[2]) {
+                queue.push(triple[2].clone());
             }
-            (prop.clone(), Property { prop_html: Piri::new(Iri::new_unchecked(&prop)).property_anchor(), target_htmls })
-        })
-        .collect()
+            triples.push(triple);
+        }
+        true
+    };
+    let previous = |node: &SimpleTerm<'_>| kb.graph.triples_matching(Any, [rdf::rest], [node]).flatten().map(|[s, _, _]| s).find(Term::is_blank_node);
+    while let Some(node) = queue.pop() {
+        let mut head = node;
+        let mut visited = BTreeSet::from([id(&head)]);
+        while let Some(previous) = previous(&head)
+            && visited.insert(id(&previous))
+        {
+            head = previous;
+        }
+        if !heads.insert(id(&head)) {
+            continue;
+        }
+        for triple in kb.graph.triples_matching(Any, Any, [&head]).flatten() {
+            if triple[0].is_blank_node() {
+                // for example the node of an enclosing list
+                describe(&triple[0].clone(), triples, &mut queue);
+            } else if !triples.iter().any(|t| t.iter().zip(&triple).all(|(a, b)| Term::eq(a, b))) {
+                triples.push(triple);
+            }
+        }
+        let mut node = Some(head);
+        while let Some(n) = node.take() {
+            if describe(&n, triples, &mut queue) {
+                node = kb.graph.triples_matching([&n], [rdf::rest], Any).flatten().map(|[_, _, o]| o).find(Term::is_blank_node);
+            }
+        }
+    }
 }
 
 #[cfg(feature = "rdfxml")]
-/// Export all triples (s,p,o) for a given subject s as RDF/XML.
+/// Export the description of a resource as RDF/XML, see [`description`].
+/// Named graphs are not marked because RDF/XML does not allow comments outside of the root element.
 pub fn serialize_rdfxml(iri: Iri<&str>) -> Result<String, Box<dyn Error>> {
-    Ok(RdfXmlSerializer::new_stringifier().serialize_triples(graph().triples_matching(Some(deskolemize(&iri)), Any, Any))?.to_string())
+    let triples = description(&kb(), &deskolemize(&iri));
+    Ok(RdfXmlSerializer::new_stringifier().serialize_triples(triples.into_iter().map(Ok::<_, Infallible>))?.to_string())
+}
+
+type TripleIter<'a> = Box<dyn Iterator<Item = Result<[SimpleTerm<'static>; 3], Infallible>> + 'a>;
+
+/// Serialize the description of a resource, see [`description`].
+/// If N-Quads or `TriG` files are loaded, the triples are grouped by graph, starting with the default graph, and each named graph group is preceded by a comment line with the graph name.
+/// The groups are serialized separately and share the header of the serialization, such as the Turtle prefixes, which is only written once.
+fn serialize_by_graph(iri: Iri<&str>, serialize: impl Fn(TripleIter<'_>) -> Result<String, Box<dyn Error>>) -> Result<String, Box<dyn Error>> {
+    let kb = kb();
+    let triples = description(&kb, &deskolemize(&iri));
+    if kb.named.is_none() {
+        return serialize(Box::new(triples.into_iter().map(Ok)));
+    }
+    // default graph first
+    let mut groups = BTreeMap::<Option<String>, Vec<[SimpleTerm<'static>; 3]>>::new();
+    for triple in triples {
+        if kb.in_default_graph(&triple) {
+            groups.entry(None).or_default().push(triple.clone());
+        }
+        for name in kb.graph_names(&triple) {
+            let comment = match name {
+                SimpleTerm::Iri(iri) => format!("<{}>", iri.as_str()),
+                SimpleTerm::BlankNode(id) => format!("_:{}", id.as_str()),
+                _ => format!("{name:?}"),
+            };
+            groups.entry(Some(comment)).or_default().push(triple.clone());
+        }
+    }
+    // the serialization of no triples only consists of the header
+    let header = serialize(Box::new(std::iter::empty()))?;
+    let mut s = header.clone();
+    for (name, group) in groups {
+        if let Some(name) = name {
+            s += "\n# named graph ";
+            s += &name;
+            s += "\n";
+        }
+        let group = serialize(Box::new(group.into_iter().map(Ok)))?;
+        s += group.strip_prefix(header.as_str()).unwrap_or(&group);
+    }
+    Ok(s)
 }
 
-/// Export all triples (s,p,o) for a given subject s as RDF Turtle using the config prefixes.
+/// Export the description of a resource as RDF Turtle using the config prefixes.
 pub fn serialize_turtle(iri: Iri<&str>) -> Result<String, Box<dyn Error>> {
-    let config = TurtleConfig::new().with_pretty(true).with_own_prefix_map(prefixes().clone());
-    Ok(TurtleSerializer::new_stringifier_with_config(config).serialize_triples(graph().triples_matching(Some(deskolemize(&iri)), Any, Any))?.to_string())
+    let config = turtle_config();
+    serialize_by_graph(iri, |triples| Ok(TurtleSerializer::new_stringifier_with_config(config.clone()).serialize_triples(triples)?.to_string()))
 }
 
-/// Export all triples (s,p,o) for a given subject s as N-Triples.
+/// Pretty Turtle using the configured prefixes.
+pub fn turtle_config() -> TurtleConfig { TurtleConfig::new().with_pretty(true).with_own_prefix_map(prefixes().clone()) }
+
+/// Export the description of a resource as N-Triples.
 pub fn serialize_nt(iri: Iri<&str>) -> Result<String, Box<dyn Error>> {
-    Ok(NtSerializer::new_stringifier().serialize_triples(graph().triples_matching(Some(deskolemize(&iri)), Any, Any))?.to_string())
+    serialize_by_graph(iri, |triples| Ok(NtSerializer::new_stringifier().serialize_triples(triples)?.to_string()))
 }
 
-fn depiction_iri(iri: Iri<&str>) -> Option<String> {
+#[cfg(feature = "jsonld")]
+/// Export the description of a resource as JSON-LD, using named graphs for triples from N-Quads, `TriG` or JSON-LD named graphs.
+/// If `compact_jsonld` is configured, IRIs are shortened using a context with the config prefixes.
+pub fn serialize_jsonld(iri: Iri<&str>) -> Result<String, Box<dyn Error>> {
+    let kb = kb();
+    let quads = description(&kb, &deskolemize(&iri)).into_iter().flat_map(|triple| {
+        let default = kb.in_default_graph(&triple).then(|| Ok::<_, Infallible>((triple.clone(), None)));
+        default.into_iter().chain(kb.graph_names(&triple).into_iter().map(move |name| Ok((triple.clone(), Some(name))))).collect::<Vec<_>>()
+    });
+    let mut serializer = JsonLdSerializer::new_stringifier_with_options(JsonLdOptions::new().with_spaces(2));
+    // the serializer panics on some graphs, such as incomplete lists, which would otherwise stop the worker
+    let expanded = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| serializer.serialize_quads(quads).map(|s| s.to_string())))
+        .map_err(|_| format!("JSON-LD serializer failed on the description of {iri}"))??;
+    if !config().compact_jsonld {
+        return Ok(expanded);
+    }
+    let context: serde_json::Map<String, serde_json::Value> =
+        prefixes().iter().map(|(p, ns)| (p.as_str().to_owned(), serde_json::Value::String(ns.as_str().to_owned()))).collect();
+    let mut graph: serde_json::Value = serde_json::from_str(&expanded)?;
+    compact_jsonld(&mut graph, None);
+    Ok(serde_json::to_string_pretty(&serde_json::json!({"@context": context, "@graph": graph}))?)
+}
+
+#[cfg(feature = "jsonld")]
+/// Shorten IRIs of expanded JSON-LD in place to compact IRIs using the config prefixes: keys as well as `@id` and `@type` values.
+/// Values of other keywords such as `@value` and `@language` are left as they are.
+fn compact_jsonld(value: &mut serde_json::Value, key: Option<&str>) {
+    use serde_json::Value;
+    let compact = |iri: &str| -> Option<String> {
+        prefixes()
+            .iter()
+            .filter_map(|(p, ns)| iri.strip_prefix(ns.as_str()).map(|suffix| (p, suffix)))
+            .filter(|(_, suffix)| !suffix.is_empty() && !suffix.starts_with("//"))
+            .min_by_key(|(_, suffix)| suffix.len())
+            .map(|(p, suffix)| format!("{}:{suffix}", p.as_str()))
+    };
+    match value {
+        Value::String(s) if matches!(key, Some("@id" | "@type")) => {
+            if let Some(c) = compact(s) {
+                *s = c;
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| compact_jsonld(v, key)),
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(k, mut v)| {
+                    if k.starts_with('@') {
+                        compact_jsonld(&mut v, Some(&k));
+                        (k, v)
+                    } else {
+                        compact_jsonld(&mut v, None);
+                        (compact(&k).unwrap_or(k), v)
+                    }
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+fn depiction_iri(kb: &Kb, iri: Iri<&str>) -> Option<String> {
     let foaf_depiction = IriRef::new_unchecked("http://xmlns.com/foaf/0.1/depiction");
-    graph()
+    kb.graph
         .triples_matching(Some(iri), Some(foaf_depiction), Any)
         .filter_map(Result::ok)
         .map(Triple::to_o)
-        .filter(Term::is_iri)
-        .map(|o| o.iri().unwrap().as_str().to_owned())
-        .next()
+        .filter_map(|o| o.iri().map(|iri| iri.as_str().to_owned()))
+        .find(|iri| linkable(iri))
+}
+
+/// Whether there are triples about the resource, that is with the resource as subject or, if inverse triples are shown, as object.
+pub fn exists(iri: Iri<&str>) -> bool {
+    let kb = kb();
+    let term = deskolemize(&iri);
+    kb.graph.triples_matching([&term], Any, Any).next().is_some()
+        || (config().show_inverse && kb.graph.triples_matching(Any, Any, [&term]).next().is_some())
 }
 
 /// Returns the resource with the given IRI from the configured namespace.
-pub fn resource(subject: Iri<&str>) -> Resource {
+/// On a static site, further property values cannot be loaded, so instead of the link only their number is shown, see [`property_page`].
+pub fn resource(subject: Iri<&str>, static_site: bool) -> Resource {
     let start = Instant::now();
+    let kb = kb();
     let piri = Piri::new(subject.as_ref());
     let suffix = piri.suffix();
-    let convert = |m: BTreeMap<String, Property>| -> Vec<_> { m.into_values().map(Property::into).collect() };
+    let convert = |m: BTreeMap<String, Property>| -> Vec<_> {
+        m.into_values()
+            .map(|mut p| {
+                if static_site
+                    && p.count > CAP
+                    && let Some(more) = p.target_htmls.last_mut()
+                {
+                    *more = format!("<span class='more'>{} more</span>", p.count - CAP);
+                }
+                p.into()
+            })
+            .collect()
+    };
 
     let source = deskolemize(&subject);
-    let mut all_directs = properties(&PropertyType::Direct, &source, 0);
+    let mut all_directs = properties(&kb, &PropertyType::Direct, &source, 0);
     let descriptions = convert(config().description_properties.iter().filter_map(|p| all_directs.remove_entry(p)).collect());
     let directs = convert(all_directs);
-    let title = titles().get(&piri.full).unwrap_or(&suffix).clone().replace(SKOLEM_START, "Blank Node ");
-    let main_type = types().get(&suffix).cloned();
-    let inverses = if config().show_inverse { convert(properties(&PropertyType::Inverse, &source, 0)) } else { Vec::new() };
+    let title = kb.title(&piri.full).unwrap_or_else(|| suffix.clone()).replace(SKOLEM_START, "Blank Node ");
+    let main_type = kb.main_type(&piri.full);
+    let download = (!suffix.is_empty()).then(|| crate::data_path(&piri.root_relative()));
+    let inverses = if config().show_inverse { convert(properties(&kb, &PropertyType::Inverse, &source, 0)) } else { Vec::new() };
     Resource {
         uri: piri.full,
         base: config().base.clone(),
         duration: format!("{:?}", start.elapsed()),
         title,
         github_issue_url: config().github.as_ref().map(|g| format!("{g}/issues/new?title={suffix}")),
+        download,
+        main_type_linkable: main_type.as_deref().is_some_and(linkable),
         main_type,
         descriptions,
         directs,
         inverses,
-        depiction: depiction_iri(subject),
+        depiction: depiction_iri(&kb, subject),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    static TEST_KB: &str = r#"
+        @prefix ex: <http://example.com/resource/> .
+        @prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+        @prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+        @prefix foaf: <http://xmlns.com/foaf/0.1/> .
+        ex:Evil rdfs:label "<script>alert(1)</script>" ;
+            rdfs:comment "</td><script>alert(2)</script>" ;
+            ex:text "x' onmouseover='alert(3)", "y\" onmouseover=\"alert(4)" ;
+            <http://example.com/resource/p'onclick='alert(5)> "value" ;
+            ex:link <javascript:alert(6)>, ex:Good ;
+            ex:typed "typed"^^<http://example.com/x'onmouseover='alert(10)> ;
+            rdf:type <javascript:alert(7)> ;
+            foaf:depiction <javascript:alert(8)> .
+        <javascript:alert(9)> ex:link ex:Evil .
+        ex:Good rdfs:label "good" ; rdf:type ex:Class ; foaf:depiction <https://example.com/good.png> .
+        ex:Cycle ex:link _:a . _:a ex:link _:b . _:b ex:link _:a .
+        ex:List ex:items ( ex:first ( ex:nested ) ex:last ) .
+        ex:Nested ex:value "0" ; ex:link [ ex:value "1" ; ex:link [ ex:value "2" ] ] .
+        ex:Referrer ex:link ex:Nested .
+        [ ex:value "r" ; ex:link ex:Nested ] .
+        ex:Deep ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:link [ ex:value "deep" ] ] ] ] ] ] ] ] ] ] ] ] .
+    "#;
+
+    /// number of `ex:member` values of `ex:Many`, which are also the subjects of `ex:memberOf ex:Many`
+    pub(crate) const MEMBERS: usize = 150;
+
+    /// Install the test knowledge base instead of the configured one, once for all tests.
+    pub(crate) fn test_kb() -> Arc<Kb> {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            let mut g = FastGraph::new();
+            g.insert_all(turtle::parse_str(TEST_KB)).unwrap();
+            // enough values to span several pages
+            for i in 0..MEMBERS {
+                g.insert(IriRef::new_unchecked(iri("Many")), IriRef::new_unchecked(iri("member")), IriRef::new_unchecked(iri(&format!("m{i}")))).unwrap();
+                g.insert(IriRef::new_unchecked(iri(&format!("m{i}"))), IriRef::new_unchecked(iri("memberOf")), IriRef::new_unchecked(iri("Many"))).unwrap();
+            }
+            *KB.write().unwrap() = Some(Arc::new(Kb::new(GraphEnum::FastGraph(g), None, Vec::new(), Duration::ZERO, None)));
+            set_status(Status::Loaded);
+        });
+        kb()
+    }
+
+    pub(crate) fn iri(suffix: &str) -> String { format!("http://example.com/resource/{suffix}") }
+
+    /// Fail if the HTML contains markup from the knowledge base.
+    fn assert_escaped(html: &str) {
+        for injected in ["<script", "onmouseover='", "onmouseover=\"", "onclick='", "href='javascript", "href=\"javascript"] {
+            assert!(!html.contains(injected), "unescaped {injected} in {html}");
+        }
+    }
+
+    fn assert_resource_escaped(r: &Resource) {
+        for (prop, values, _) in r.descriptions.iter().chain(&r.directs).chain(&r.inverses) {
+            assert_escaped(prop);
+            for value in values {
+                assert_escaped(value);
+            }
+        }
+    }
+
+    #[test]
+    fn escape_literals() {
+        test_kb();
+        let r = resource(Iri::new_unchecked(&iri("Evil")), false);
+        assert_resource_escaped(&r);
+        // the title is escaped by the template
+        assert_eq!(r.title, "<script>alert(1)</script>");
+        let html = r.descriptions.iter().chain(&r.directs).flat_map(|(_, values, _)| values).cloned().collect::<String>();
+        assert!(html.contains("&lt;/td&gt;&lt;script&gt;alert(2)&lt;/script&gt;"), "{html}");
+        assert!(html.contains("x&#39; onmouseover=&#39;alert(3)"), "{html}");
+        assert!(html.contains("y&quot; onmouseover=&quot;alert(4)"), "{html}");
+        assert!(html.contains("x&#39;onmouseover=&#39;alert(10)"), "{html}");
+        let props = r.directs.iter().map(|(prop, ..)| prop.clone()).collect::<String>();
+        assert!(props.contains("p&#39;onclick=&#39;alert(5)"), "{props}");
+    }
+
+    #[test]
+    fn no_links_to_other_schemes() {
+        test_kb();
+        let r = resource(Iri::new_unchecked(&iri("Evil")), false);
+        assert_resource_escaped(&r);
+        let html = r.directs.iter().flat_map(|(_, values, _)| values).cloned().collect::<String>();
+        assert!(html.contains("&lt;javascript:alert(6)&gt;"), "{html}");
+        assert!(html.contains("href='/Good'"), "{html}");
+        assert!(!r.inverses.is_empty());
+        assert_eq!(r.main_type.as_deref(), Some("javascript:alert(7)"));
+        assert!(!r.main_type_linkable);
+        assert_eq!(r.depiction, None);
+
+        let good = resource(Iri::new_unchecked(&iri("Good")), false);
+        assert!(good.main_type_linkable);
+        assert_eq!(good.depiction.as_deref(), Some("https://example.com/good.png"));
+    }
+
+    #[test]
+    fn escape_property_page() {
+        test_kb();
+        let evil = iri("Evil");
+        for (prop, inverse) in
+            [(iri("text"), false), (iri("typed"), false), (iri("link"), false), (iri("link"), true), (iri("p'onclick='alert(5)"), false)]
+        {
+            let html = property_page(Iri::new_unchecked(&evil), &prop, inverse, 0).unwrap();
+            assert!(!html.is_empty(), "{prop}");
+            assert_escaped(&html);
+        }
+    }
+
+    #[test]
+    fn linkable_schemes() {
+        assert!(linkable("http://example.com/a"));
+        assert!(linkable("HTTPS://example.com/a"));
+        assert!(linkable("/relative/path:with/colon"));
+        assert!(linkable("?prop=a:b"));
+        assert!(!linkable("javascript:alert(1)"));
+        assert!(!linkable("JavaScript:alert(1)"));
+        assert!(!linkable("data:text/html,<script>alert(1)</script>"));
+        assert!(!linkable("mailto:someone@example.com"));
+    }
+
+    #[test]
+    fn blank_node_cycle() {
+        test_kb();
+        let r = resource(Iri::new_unchecked(&iri("Cycle")), false);
+        let html = &r.directs[0].1[0];
+        // the nested tables stop at the maximum depth
+        assert_eq!(html.matches("<table>").count(), 10);
+        assert!(html.contains("..."));
+    }
+
+    /// The triples of the bounded description in the given mode, sorted, with local names of the namespace and `_` for blank nodes.
+    fn described(suffix: &str, mode: BoundedDescription, inverse_limit: usize) -> Vec<String> {
+        let kb = test_kb();
+        let term = |t: &SimpleTerm<'_>| match t {
+            SimpleTerm::Iri(i) => i.as_str().trim_start_matches("http://example.com/resource/").to_owned(),
+            SimpleTerm::BlankNode(_) => "_".to_owned(),
+            SimpleTerm::LiteralDatatype(lex, _) => format!("\"{lex}\""),
+            _ => format!("{t:?}"),
+        };
+        let source = SimpleTerm::Iri(IriRef::new_unchecked(iri(suffix).into()));
+        let mut triples: Vec<String> =
+            bounded_description(&kb, &source, mode, inverse_limit).iter().map(|[s, p, o]| format!("{} {} {}", term(s), term(p), term(o))).collect();
+        triples.sort();
+        triples
+    }
+
+    #[test]
+    fn bounded_descriptions() {
+        assert_eq!(described("Nested", BoundedDescription::Subject, 0), ["Nested link _", "Nested value \"0\""]);
+        let cbd = ["Nested link _", "Nested value \"0\"", "_ link _", "_ value \"1\"", "_ value \"2\""];
+        assert_eq!(described("Nested", BoundedDescription::Cbd, 0), cbd);
+        // the inverse triple of the blank node subject is included with its other triples, inverse triples of the nested blank nodes are already included
+        let mut scbd: Vec<_> = cbd.into_iter().chain(["Referrer link Nested", "_ link Nested", "_ value \"r\""]).collect();
+        scbd.sort_unstable();
+        assert_eq!(described("Nested", BoundedDescription::Symmetric, 100), scbd);
+    }
+
+    #[test]
+    fn bounded_description_depth() {
+        // unlike the nested tables on the HTML page, the concise bounded description has no maximum depth
+        let cbd = described("Deep", BoundedDescription::Cbd, 0);
+        assert_eq!(cbd.len(), 13);
+        assert!(cbd.contains(&"_ value \"deep\"".to_owned()));
+        let r = resource(Iri::new_unchecked(&iri("Deep")), false);
+        let html = &r.directs[0].1[0];
+        assert_eq!(html.matches("<table>").count(), 10);
+        assert!(html.contains("...") && !html.contains("deep"));
+    }
+
+    #[test]
+    fn max_inverse_triples() {
+        let direct = described("Many", BoundedDescription::Cbd, 0);
+        assert_eq!(direct.len(), MEMBERS);
+        let inverse = |limit| described("Many", BoundedDescription::Symmetric, limit).into_iter().filter(|t| t.contains(" memberOf ")).count();
+        assert_eq!(inverse(0), 0);
+        assert_eq!(inverse(10), 10);
+        assert_eq!(inverse(MEMBERS), MEMBERS);
+        assert_eq!(inverse(MEMBERS + 1), MEMBERS);
+        assert_eq!(described("Many", BoundedDescription::Symmetric, 10).len(), MEMBERS + 10);
+    }
+
+    #[cfg(feature = "jsonld")]
+    #[test]
+    fn jsonld_list_node() {
+        let kb = test_kb();
+        let [tail, _, _] = kb.graph.triples_matching(Any, [rdf::first], [SimpleTerm::Iri(IriRef::new_unchecked(iri("last").into()))]).flatten().next().unwrap();
+        let skolem = format!("{}{SKOLEM_START}{}", config().namespace.as_str(), tail.bnode_id().unwrap().as_str());
+        let jsonld = serialize_jsonld(Iri::new_unchecked(&skolem)).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&jsonld).unwrap();
+        // the whole list with the nested one and the triple that refers to its head
+        for uri in ["List", "items", "first", "nested", "last"] {
+            assert!(json.to_string().contains(&iri(uri)), "{uri} missing in {jsonld}");
+        }
     }
 }
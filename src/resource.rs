@@ -8,13 +8,17 @@ pub struct Resource {
     //pub suffix: String,
     pub title: String,
     pub main_type: Option<String>,
-    /// HTML representations of properties and descriptions of this resource.
-    pub descriptions: Vec<(String, Vec<String>)>,
-    /// HTML representations of properties and objects of triples where this resource is a subject.
-    pub directs: Vec<(String, Vec<String>)>,
-    /// HTML representations of subjects and properties of triples where this resource is an object.
-    pub inverses: Vec<(String, Vec<String>)>,
+    /// whether the main type is shown as a link, see [`crate::rdf::linkable`]
+    pub main_type_linkable: bool,
+    /// HTML representations of properties and descriptions of this resource with the number of values if there is more than one.
+    pub descriptions: Vec<(String, Vec<String>, Option<usize>)>,
+    /// HTML representations of properties and objects of triples where this resource is a subject with the number of objects if there is more than one.
+    pub directs: Vec<(String, Vec<String>, Option<usize>)>,
+    /// HTML representations of subjects and properties of triples where this resource is an object with the number of subjects if there is more than one.
+    pub inverses: Vec<(String, Vec<String>, Option<usize>)>,
     pub duration: String,
     pub github_issue_url: Option<String>,
+    /// root-relative path of the RDF documents that describe this resource, to which the file extension is appended, None for the namespace itself
+    pub download: Option<String>,
     pub depiction: Option<String>,
 }
@@ -0,0 +1,126 @@
+//! Full-text search over the titles and descriptions of resources, see [`Index`].
+use crate::config::config;
+use crate::rdf::{Kb, Piri, escape, linkable};
+use log::{error, info};
+use serde::Serialize;
+use sophia::api::term::matcher::Any;
+use sophia::api::term::{SimpleTerm, Term};
+use sophia::iri::{Iri, IriRef};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::Instant;
+
+/// Resource found by a search.
+#[derive(Serialize)]
+pub struct Hit {
+    pub uri: String,
+    pub title: String,
+    /// where the resource is shown, relative to the server root for resources in the namespace, None if the IRI is not [linkable]
+    pub href: Option<String>,
+}
+
+/// Maps lower-case words in titles and descriptions to the resources that contain them.
+/// Built once per knowledge base on the first search, which needs the titles to be indexed.
+pub struct Index {
+    /// resource URIs with their titles
+    resources: Vec<(String, Option<String>)>,
+    /// words with the positions of the resources in which they occur and whether they occur in the title
+    words: BTreeMap<String, Vec<(u32, bool)>>,
+}
+
+/// Lower-case alphanumeric words of a text.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ { text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase) }
+
+impl Index {
+    pub fn new(kb: &Kb) -> Index {
+        let t = Instant::now();
+        let mut positions = HashMap::<String, u32>::new();
+        let mut index = Index { resources: Vec::new(), words: BTreeMap::new() };
+        let mut add = |uri: &str, title: Option<&str>, text: &str| {
+            let position = *positions.entry(uri.to_owned()).or_insert_with(|| {
+                index.resources.push((uri.to_owned(), None));
+                u32::try_from(index.resources.len() - 1).expect("less than 2^32 resources")
+            });
+            if title.is_some() {
+                index.resources[position as usize].1 = title.map(str::to_owned);
+            }
+            for word in words(text) {
+                let entries = index.words.entry(word).or_default();
+                // repeated words, duplicates from different descriptions don't matter
+                if entries.last() != Some(&(position, title.is_some())) {
+                    entries.push((position, title.is_some()));
+                }
+            }
+        };
+        for (uri, title) in kb.titles() {
+            add(uri, Some(title), title);
+        }
+        for prop in &config().description_properties {
+            let Ok(iref) = IriRef::new(prop.clone().into()) else {
+                error!("Skipping invalid description property {prop}");
+                continue;
+            };
+            for t in kb.graph.triples_matching(Any, [SimpleTerm::Iri(iref)], Any).flatten() {
+                if let Some(iri) = t[0].iri()
+                    && let Some(lit) = t[2].lexical_form()
+                {
+                    add(iri.as_str(), None, &lit);
+                }
+            }
+        }
+        info!("Indexed {} words of {} resources for search in {:?}", index.words.len(), index.resources.len(), t.elapsed());
+        index
+    }
+
+    /// Total number of resources that contain words starting with each of the words of the query and the first `limit` of them.
+    /// Resources whose title contains more of the words come first, followed by those with the query at the start of the title and then shorter titles.
+    pub fn search(&self, query: &str, limit: usize) -> (usize, Vec<Hit>) {
+        // position -> number of query words in the title
+        let mut scores: Option<HashMap<u32, usize>> = None;
+        for word in words(query) {
+            let mut matches = HashMap::<u32, usize>::new();
+            for (_, entries) in self.words.range(word.clone()..).take_while(|(w, _)| w.starts_with(&word)) {
+                for &(position, in_title) in entries {
+                    let score = matches.entry(position).or_default();
+                    *score = (*score).max(usize::from(in_title));
+                }
+            }
+            scores = Some(match scores {
+                None => matches,
+                Some(scores) => scores.into_iter().filter_map(|(position, score)| Some((position, score + matches.get(&position)?))).collect(),
+            });
+        }
+        let query = query.trim().to_lowercase();
+        let mut hits: Vec<_> = scores.unwrap_or_default().into_iter().map(|(position, score)| (score, &self.resources[position as usize])).collect();
+        hits.sort_by_cached_key(|(score, (uri, title))| {
+            let title = title.as_deref().unwrap_or(uri).to_lowercase();
+            (Reverse(*score), !title.starts_with(&query), title.len(), title)
+        });
+        let total = hits.len();
+        let hits = hits
+            .into_iter()
+            .take(limit)
+            .map(|(_, (uri, title))| {
+                let piri = Piri::new(Iri::new_unchecked(uri));
+                Hit { uri: uri.clone(), title: title.clone().unwrap_or_else(|| piri.suffix()), href: linkable(uri).then(|| piri.root_relative()) }
+            })
+            .collect();
+        (total, hits)
+    }
+}
+
+/// HTML list of search results, out of `total` matching resources.
+pub fn html(query: &str, total: usize, hits: &[Hit]) -> String {
+    let shown = if hits.len() < total { format!(", showing the first {}", hits.len()) } else { String::new() };
+    let mut s = format!("<p>{total} results for <b>{}</b>{shown}</p><ul class='search'>", escape(query));
+    for hit in hits {
+        let title = match &hit.href {
+            Some(href) => format!("<a href='{}'>{}</a>", escape(href), escape(&hit.title)),
+            None => escape(&hit.title),
+        };
+        let _ = write!(s, "<li>{title} <span class='uri'>{}</span></li>", escape(&hit.uri));
+    }
+    s.push_str("</ul>");
+    s
+}
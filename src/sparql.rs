@@ -0,0 +1,451 @@
+//! Built-in SPARQL endpoint over the knowledge base, see <https://www.w3.org/TR/sparql11-protocol/>.
+//! SELECT queries are evaluated by Sophia, ASK, CONSTRUCT and DESCRIBE queries are answered based on the solutions of their graph pattern.
+//! Only the merged default graph is queried, named graphs are not available.
+//! At most one query per CPU is evaluated at the same time.
+use crate::config::config;
+use crate::negotiate::negotiate_media_type;
+use crate::rdf::{GraphEnum, Kb, Piri, escape, kb, turtle_config};
+use serde_json::{Value, json};
+use sophia::api::graph::{GResult, Graph, MutableGraph};
+use sophia::api::serializer::{Stringifier, TripleSerializer};
+use sophia::api::sparql::SparqlDataset;
+use sophia::api::term::bnode_id::BnodeId;
+use sophia::api::term::matcher::{Any, TermMatcher};
+use sophia::api::term::{FromTerm, LanguageTag, SimpleTerm, Term};
+use sophia::api::triple::Triple;
+use sophia::inmem::graph::FastGraph;
+use sophia::iri::{Iri, IriRef};
+use sophia::sparql::{SparqlQuery, SparqlWrapper, SparqlWrapperError};
+use sophia::turtle::serializer::nt::NtSerializer;
+use sophia::turtle::serializer::turtle::TurtleSerializer;
+use spargebra::Query;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+pub const HTML: &str = "text/html";
+const JSON: &str = "application/sparql-results+json";
+const XML: &str = "application/sparql-results+xml";
+const CSV: &str = "text/csv";
+const TTL: &str = "text/turtle";
+const NT: &str = "application/n-triples";
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+static EXAMPLE_QUERY: &str = "SELECT * WHERE {\n  ?s ?p ?o\n} LIMIT 10";
+/// Number of queries that are currently evaluated, see [`Permit`].
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+/// Maximum number of queries that are evaluated at the same time.
+static MAX_RUNNING: LazyLock<usize> = LazyLock::new(|| std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get));
+
+/// Why a query could not be answered.
+#[derive(Debug)]
+pub enum QueryError {
+    /// invalid or unsupported query
+    BadRequest(String),
+    /// none of the offered media types for this query form is acceptable
+    NotAcceptable(&'static [&'static str]),
+    Timeout(Duration),
+    /// the maximum number of queries is already being evaluated
+    Busy,
+    Internal(String),
+}
+
+/// Permission to evaluate a query, which only a limited number of queries get at the same time.
+struct Permit;
+
+impl Permit {
+    /// None if the maximum number of queries is already being evaluated.
+    fn acquire() -> Option<Permit> {
+        RUNNING.fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| (running < *MAX_RUNNING).then_some(running + 1)).ok().map(|_| Permit)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) { RUNNING.fetch_sub(1, Ordering::AcqRel); }
+}
+
+/// Error of a [`DeadlineGraph`].
+#[derive(Debug)]
+enum GraphError {
+    Timeout,
+    Graph(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Timeout => write!(f, "timeout"),
+            GraphError::Graph(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Graph that fails once the deadline has passed, so that the evaluation is also aborted while Sophia matches the triple patterns of a query before it returns the first solution.
+struct DeadlineGraph<'a, G> {
+    graph: &'a G,
+    deadline: Instant,
+}
+
+impl<G: Graph> Graph for DeadlineGraph<'_, G> {
+    type Triple<'x>
+        = G::Triple<'x>
+    where Self: 'x;
+    type Error = GraphError;
+
+    fn triples(&self) -> impl Iterator<Item = GResult<Self, Self::Triple<'_>>> + '_ { self.triples_matching(Any, Any, Any) }
+
+    fn triples_matching<'s, S, P, O>(&'s self, sm: S, pm: P, om: O) -> impl Iterator<Item = GResult<Self, Self::Triple<'s>>> + 's
+    where
+        S: TermMatcher + 's,
+        P: TermMatcher + 's,
+        O: TermMatcher + 's,
+    {
+        self.graph.triples_matching(sm, pm, om).map(|t| {
+            if Instant::now() > self.deadline {
+                return Err(GraphError::Timeout);
+            }
+            t.map_err(|e| GraphError::Graph(e.to_string()))
+        })
+    }
+}
+
+/// Query result serialized in the negotiated media type.
+/// HTML results are a fragment to be shown below the query form.
+pub struct Answer {
+    pub media_type: &'static str,
+    pub body: String,
+}
+
+/// Variables and their values in each solution of a SELECT query.
+struct Solutions {
+    vars: Vec<String>,
+    /// at most `sparql_max_rows`
+    rows: Vec<Vec<Option<SimpleTerm<'static>>>>,
+    /// whether solutions were left out because there are more than `sparql_max_rows`
+    truncated: bool,
+}
+
+/// Answer a SPARQL query in the format that best matches the Accept header.
+/// Blocks until the query is answered or the configured timeout is reached, in which case the evaluation is aborted.
+pub fn query(text: &str, accept: Option<&str>) -> Result<Answer, QueryError> {
+    let mut query = Query::parse(text, None).map_err(|e| QueryError::BadRequest(e.to_string()))?;
+    if let Query::Select { pattern, .. } = &mut query
+        && is_select_star(text)
+    {
+        restore_star_order(pattern);
+    }
+    let (Query::Select { dataset, .. } | Query::Ask { dataset, .. } | Query::Construct { dataset, .. } | Query::Describe { dataset, .. }) = &query;
+    if dataset.is_some() {
+        return Err(QueryError::BadRequest("FROM and FROM NAMED are not supported, only the default graph can be queried.".to_owned()));
+    }
+    let offered: &'static [&'static str] = match query {
+        Query::Select { .. } => &[JSON, XML, CSV, HTML],
+        Query::Ask { .. } => &[JSON, XML, HTML],
+        Query::Construct { .. } | Query::Describe { .. } => &[TTL, NT, HTML],
+    };
+    let media_type = negotiate_media_type(accept, offered).ok_or(QueryError::NotAcceptable(offered))?;
+    let _permit = Permit::acquire().ok_or(QueryError::Busy)?;
+    let deadline = Instant::now() + Duration::from_secs(config().sparql_timeout);
+    evaluate(&kb(), query, media_type, deadline).map(|body| Answer { media_type, body })
+}
+
+/// Whether the variables of the main query are selected with `SELECT *`, which the parsed query does not tell.
+fn is_select_star(text: &str) -> bool {
+    let text = text.replace('*', " * ");
+    // prologue IRIs contain no whitespace, comments start at # outside of IRIs
+    let mut tokens = text.lines().flat_map(|line| line.split_whitespace().take_while(|t| !t.starts_with('#')));
+    tokens.any(|t| t.eq_ignore_ascii_case("SELECT"))
+        && tokens.find(|t| !t.eq_ignore_ascii_case("DISTINCT") && !t.eq_ignore_ascii_case("REDUCED")) == Some("*")
+}
+
+/// Spargebra sorts the variables of `SELECT *` by name, so put them back in the order in which they appear in the pattern, which determines the columns of the results.
+fn restore_star_order(pattern: &mut GraphPattern) {
+    match pattern {
+        GraphPattern::Slice { inner, .. } | GraphPattern::Distinct { inner } | GraphPattern::Reduced { inner } => restore_star_order(inner),
+        GraphPattern::Project { inner, variables } => {
+            let mut ordered = Vec::with_capacity(variables.len());
+            inner.on_in_scope_variable(|v| {
+                if variables.iter().any(|x| x == v) && ordered.iter().all(|x| x != v) {
+                    ordered.push(v.clone());
+                }
+            });
+            if ordered.len() == variables.len() {
+                *variables = ordered;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn timeout() -> QueryError { QueryError::Timeout(Duration::from_secs(config().sparql_timeout)) }
+
+/// HTML form to enter a query, prefilled with the given one or an example.
+pub fn form(query: &str) -> String {
+    let query = if query.is_empty() { EXAMPLE_QUERY } else { query };
+    format!(
+        "<form action='sparql' method='get'><textarea name='query' rows='12' style='width: 100%'>{}</textarea><br><input type='submit' value='Run query'></form>",
+        escape(query)
+    )
+}
+
+fn evaluate(kb: &Kb, query: Query, media_type: &str, deadline: Instant) -> Result<String, QueryError> {
+    match &kb.graph {
+        GraphEnum::FastGraph(g) => evaluate_graph(g, query, media_type, deadline),
+        #[cfg(feature = "hdt")]
+        GraphEnum::HdtGraph(g) => evaluate_graph(g, query, media_type, deadline),
+        GraphEnum::EndpointGraph(g) => Err(QueryError::BadRequest(format!("Please query the SPARQL endpoint {} directly.", g.endpoint))),
+    }
+}
+
+fn evaluate_graph<G: Graph>(g: &G, query: Query, media_type: &str, deadline: Instant) -> Result<String, QueryError> {
+    match query {
+        Query::Select { .. } => {
+            let solutions = select(g, query, deadline)?;
+            Ok(match media_type {
+                XML => solutions.xml(),
+                CSV => solutions.csv(),
+                HTML => solutions.html(),
+                _ => solutions.json().to_string(),
+            })
+        }
+        Query::Ask { dataset, pattern, base_iri } => {
+            let pattern = GraphPattern::Slice { inner: Box::new(pattern), start: 0, length: Some(1) };
+            let b = !select(g, Query::Select { dataset, pattern, base_iri }, deadline)?.rows.is_empty();
+            Ok(match media_type {
+                XML => format!("<?xml version=\"1.0\"?>\n<sparql xmlns=\"http://www.w3.org/2005/sparql-results#\"><head/><boolean>{b}</boolean></sparql>"),
+                HTML => format!("<p>{b}</p>"),
+                _ => json!({"head": {}, "boolean": b}).to_string(),
+            })
+        }
+        Query::Construct { template, dataset, pattern, base_iri } => {
+            let solutions = select(g, Query::Select { dataset, pattern, base_iri }, deadline)?;
+            serialize(&construct(&template, &solutions)?, media_type)
+        }
+        Query::Describe { dataset, pattern, base_iri } => {
+            let solutions = select(g, Query::Select { dataset, pattern, base_iri }, deadline)?;
+            let mut described = HashSet::new();
+            let mut graph = FastGraph::new();
+            for term in solutions.rows.iter().flatten().flatten() {
+                if term.is_literal() || !described.insert(format!("{term:?}")) {
+                    continue;
+                }
+                for t in g.triples_matching([term], Any, Any).flatten() {
+                    graph.insert(t.s(), t.p(), t.o()).map_err(|e| QueryError::Internal(e.to_string()))?;
+                }
+                if Instant::now() > deadline {
+                    return Err(timeout());
+                }
+            }
+            serialize(&graph, media_type)
+        }
+    }
+}
+
+/// At most `sparql_max_rows` solutions of a SELECT query, aborted at the deadline.
+fn select<G: Graph>(g: &G, query: Query, deadline: Instant) -> Result<Solutions, QueryError> {
+    let graph = DeadlineGraph { graph: g, deadline };
+    let dataset = graph.as_dataset();
+    let wrapper = SparqlWrapper(&dataset);
+    // errors before the evaluation starts are caused by the query, for example unsupported features
+    let error = |e: SparqlWrapperError<GraphError>, before: bool| match e {
+        SparqlWrapperError::Dataset(GraphError::Timeout) => timeout(),
+        SparqlWrapperError::Dataset(e) => QueryError::Internal(e.to_string()),
+        e if before => QueryError::BadRequest(e.to_string()),
+        e => QueryError::Internal(e.to_string()),
+    };
+    let bindings = wrapper.query(&SparqlQuery::from(query)).map_err(|e| error(e, true))?.into_bindings();
+    let vars = bindings.variables().into_iter().map(str::to_owned).collect();
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    for row in bindings {
+        if Instant::now() > deadline {
+            return Err(timeout());
+        }
+        if rows.len() == config().sparql_max_rows {
+            truncated = true;
+            break;
+        }
+        let row = row.map_err(|e| error(e, false))?;
+        rows.push(row.into_iter().map(|t| t.map(|t| SimpleTerm::from_term(t.as_simple()))).collect());
+    }
+    Ok(Solutions { vars, rows, truncated })
+}
+
+/// Instantiate the template for each solution, skipping triples with unbound variables or invalid terms.
+/// Blank nodes in the template are new for each solution.
+fn construct(template: &[TriplePattern], solutions: &Solutions) -> Result<FastGraph, QueryError> {
+    let mut graph = FastGraph::new();
+    for (i, row) in solutions.rows.iter().enumerate() {
+        let value = |v: &spargebra::term::Variable| solutions.vars.iter().position(|x| x == v.as_str()).and_then(|j| row[j].clone());
+        for tp in template {
+            let p = match &tp.predicate {
+                NamedNodePattern::NamedNode(n) => Some(SimpleTerm::Iri(IriRef::new_unchecked(n.as_str().to_owned().into()))),
+                NamedNodePattern::Variable(v) => value(v).filter(Term::is_iri),
+            };
+            let s = instantiate(&tp.subject, i, &value).filter(|s| s.is_iri() || s.is_blank_node());
+            if let (Some(s), Some(p), Some(o)) = (s, p, instantiate(&tp.object, i, &value)) {
+                graph.insert(&s, &p, &o).map_err(|e| QueryError::Internal(e.to_string()))?;
+            }
+        }
+    }
+    Ok(graph)
+}
+
+fn instantiate(t: &TermPattern, row: usize, value: &impl Fn(&spargebra::term::Variable) -> Option<SimpleTerm<'static>>) -> Option<SimpleTerm<'static>> {
+    match t {
+        TermPattern::NamedNode(n) => Some(SimpleTerm::Iri(IriRef::new_unchecked(n.as_str().to_owned().into()))),
+        TermPattern::BlankNode(b) => Some(SimpleTerm::BlankNode(BnodeId::new_unchecked(format!("{}_{row}", b.as_str()).into()))),
+        TermPattern::Literal(l) => Some(match l.language() {
+            Some(lang) => SimpleTerm::LiteralLanguage(l.value().to_owned().into(), LanguageTag::new_unchecked(lang.to_owned().into())),
+            None => SimpleTerm::LiteralDatatype(l.value().to_owned().into(), IriRef::new_unchecked(l.datatype().as_str().to_owned().into())),
+        }),
+        TermPattern::Variable(v) => value(v),
+        // quoted triples are not supported
+        TermPattern::Triple(_) => None,
+    }
+}
+
+fn serialize(graph: &FastGraph, media_type: &str) -> Result<String, QueryError> {
+    let turtle = || -> Result<String, QueryError> {
+        Ok(TurtleSerializer::new_stringifier_with_config(turtle_config())
+            .serialize_graph(graph)
+            .map_err(|e| QueryError::Internal(e.to_string()))?
+            .to_string())
+    };
+    match media_type {
+        NT => Ok(NtSerializer::new_stringifier().serialize_graph(graph).map_err(|e| QueryError::Internal(e.to_string()))?.to_string()),
+        HTML => Ok(format!("<pre>{}</pre>", escape(&turtle()?))),
+        _ => turtle(),
+    }
+}
+
+impl Solutions {
+    /// SPARQL 1.1 Query Results JSON Format
+    fn json(&self) -> Value {
+        let bindings: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut binding = serde_json::Map::new();
+                for (var, term) in self.vars.iter().zip(row) {
+                    if let Some(term) = term {
+                        binding.insert(var.clone(), json_term(term));
+                    }
+                }
+                Value::Object(binding)
+            })
+            .collect();
+        json!({"head": {"vars": self.vars}, "results": {"bindings": bindings}})
+    }
+
+    /// SPARQL Query Results XML Format
+    fn xml(&self) -> String {
+        let mut s = "<?xml version=\"1.0\"?>\n<sparql xmlns=\"http://www.w3.org/2005/sparql-results#\">\n<head>".to_owned();
+        for var in &self.vars {
+            let _ = write!(s, "<variable name=\"{}\"/>", escape(var));
+        }
+        s += "</head>\n<results>\n";
+        for row in &self.rows {
+            s += "<result>";
+            for (var, term) in self.vars.iter().zip(row) {
+                if let Some(term) = term {
+                    let _ = write!(s, "<binding name=\"{}\">{}</binding>", escape(var), xml_term(term));
+                }
+            }
+            s += "</result>\n";
+        }
+        s += "</results>\n</sparql>";
+        s
+    }
+
+    /// SPARQL 1.1 Query Results CSV Format, which loses the datatypes and language tags
+    fn csv(&self) -> String {
+        let line = |fields: Vec<String>| fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",") + "\r\n";
+        let mut s = line(self.vars.clone());
+        for row in &self.rows {
+            s += &line(row.iter().map(|t| t.as_ref().map(plain_term).unwrap_or_default()).collect());
+        }
+        s
+    }
+
+    /// HTML table with links to the resources
+    fn html(&self) -> String {
+        let mut s = "<table><tr>".to_owned();
+        for var in &self.vars {
+            let _ = write!(s, "<th>{}</th>", escape(var));
+        }
+        s += "</tr>";
+        for row in &self.rows {
+            s += "<tr>";
+            for term in row {
+                let _ = write!(s, "<td>{}</td>", term.as_ref().map(html_term).unwrap_or_default());
+            }
+            s += "</tr>";
+        }
+        if self.truncated {
+            let _ = write!(s, "</table><p>first {} results</p>", self.rows.len());
+        } else {
+            let _ = write!(s, "</table><p>{} results</p>", self.rows.len());
+        }
+        s
+    }
+}
+
+fn json_term(t: &SimpleTerm<'_>) -> Value {
+    match t {
+        SimpleTerm::Iri(iri) => json!({"type": "uri", "value": iri.as_str()}),
+        SimpleTerm::BlankNode(id) => json!({"type": "bnode", "value": id.as_str()}),
+        SimpleTerm::LiteralLanguage(lit, tag) => json!({"type": "literal", "value": &lit[..], "xml:lang": tag.as_str()}),
+        SimpleTerm::LiteralDatatype(lit, dt) if dt.as_str() == XSD_STRING => json!({"type": "literal", "value": &lit[..]}),
+        SimpleTerm::LiteralDatatype(lit, dt) => json!({"type": "literal", "value": &lit[..], "datatype": dt.as_str()}),
+        SimpleTerm::Triple(spo) => {
+            json!({"type": "triple", "value": {"subject": json_term(&spo[0]), "predicate": json_term(&spo[1]), "object": json_term(&spo[2])}})
+        }
+        SimpleTerm::Variable(v) => json!({"type": "literal", "value": v.as_str()}),
+    }
+}
+
+fn xml_term(t: &SimpleTerm<'_>) -> String {
+    match t {
+        SimpleTerm::Iri(iri) => format!("<uri>{}</uri>", escape(iri.as_str())),
+        SimpleTerm::BlankNode(id) => format!("<bnode>{}</bnode>", escape(id.as_str())),
+        SimpleTerm::LiteralLanguage(lit, tag) => format!("<literal xml:lang=\"{}\">{}</literal>", escape(tag.as_str()), escape(lit)),
+        SimpleTerm::LiteralDatatype(lit, dt) if dt.as_str() == XSD_STRING => format!("<literal>{}</literal>", escape(lit)),
+        SimpleTerm::LiteralDatatype(lit, dt) => format!("<literal datatype=\"{}\">{}</literal>", escape(dt.as_str()), escape(lit)),
+        SimpleTerm::Triple(spo) => format!(
+            "<triple><subject>{}</subject><predicate>{}</predicate><object>{}</object></triple>",
+            xml_term(&spo[0]),
+            xml_term(&spo[1]),
+            xml_term(&spo[2])
+        ),
+        SimpleTerm::Variable(v) => format!("<literal>{}</literal>", escape(v.as_str())),
+    }
+}
+
+/// IRI, lexical form or blank node label
+fn plain_term(t: &SimpleTerm<'_>) -> String {
+    match t {
+        SimpleTerm::Iri(iri) => iri.as_str().to_owned(),
+        SimpleTerm::BlankNode(id) => format!("_:{}", id.as_str()),
+        SimpleTerm::LiteralLanguage(lit, _) | SimpleTerm::LiteralDatatype(lit, _) => lit.to_string(),
+        _ => format!("{t:?}"),
+    }
+}
+
+fn csv_field(s: &str) -> String { if s.contains([',', '"', '\r', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_owned() } }
+
+fn html_term(t: &SimpleTerm<'_>) -> String {
+    match t {
+        SimpleTerm::Iri(iri) => {
+            let piri = Piri::new(Iri::new_unchecked(iri.as_str()));
+            piri.anchor(&piri.short(), "")
+        }
+        SimpleTerm::LiteralLanguage(lit, tag) => format!("{} @{}", escape(lit), escape(tag.as_str())),
+        _ => escape(&plain_term(t)),
+    }
+}
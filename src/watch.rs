@@ -0,0 +1,62 @@
+//! Reload the knowledge base when its local files change.
+use crate::config::config;
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+pub type Fingerprint = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+/// Path, modification time and size of all local knowledge base files, including those within directories.
+/// URLs are not checked.
+pub fn fingerprint() -> Fingerprint {
+    let mut fp = Fingerprint::new();
+    let mut add = |p: &Path| {
+        if let Ok(m) = std::fs::metadata(p) {
+            fp.push((p.to_owned(), m.modified().ok(), m.len()));
+        }
+    };
+    for filename in config().kb_file.iter().filter(|f| !f.starts_with("http")) {
+        let path = Path::new(filename);
+        if path.is_dir() {
+            if let Ok(entries) = std::fs::read_dir(path) {
+                let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+                paths.sort();
+                for p in &paths {
+                    add(p);
+                }
+            }
+        } else {
+            add(path);
+        }
+    }
+    fp
+}
+
+/// Start a background thread that checks the knowledge base files every `interval` and calls `reload` when they have changed.
+/// Waits until the files are unchanged for one more interval so that files that are still being written are not loaded.
+pub fn spawn(interval: Duration, reload: fn()) {
+    info!("Checking knowledge base files for changes every {interval:?}");
+    thread::spawn(move || {
+        let mut last = fingerprint();
+        loop {
+            thread::sleep(interval);
+            let mut current = fingerprint();
+            if current == last {
+                continue;
+            }
+            debug!("Knowledge base files changed, waiting for writes to finish");
+            loop {
+                thread::sleep(interval);
+                let next = fingerprint();
+                if next == current {
+                    break;
+                }
+                current = next;
+            }
+            info!("Knowledge base files changed");
+            reload();
+            last = current;
+        }
+    });
+}
@@ -0,0 +1,26 @@
+//! Smoke test of `rickview export` with the example knowledge base.
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn export_example_kb() {
+    let dir = std::env::temp_dir().join(format!("rickview-export-{}", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_rickview"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("RICKVIEW_LOG_LEVEL", "warn")
+        .arg("export")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    let exists = |name: &str| Path::new(&dir).join(name).is_file();
+    let files = ["index.html", "about.html", "classes.html", "rickview.css", ".nojekyll", "ExClass.html", "ExClass.ttl", "ExClass.nt", "ExInstance.html"];
+    let formats = [(cfg!(feature = "rdfxml"), "ExClass.rdf"), (cfg!(feature = "jsonld"), "ExClass.jsonld")];
+    let files = files.into_iter().chain(formats.into_iter().filter_map(|(enabled, name)| enabled.then_some(name)));
+    let missing: Vec<_> = files.filter(|name| !exists(name)).collect();
+    let genid = Path::new(&dir).join(".well-known/genid");
+    let blank_nodes = std::fs::read_dir(&genid).map(Iterator::count).unwrap_or_default();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(output.status.success(), "export failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(missing.is_empty(), "missing {missing:?}");
+    assert!(blank_nodes > 0, "no blank node pages in {}", genid.display());
+}